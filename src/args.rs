@@ -0,0 +1,151 @@
+use std::{env, fs};
+
+use crate::{config::Config, game::{Difficulty, Game, Topology}, keymap::Keymap, render::{CELL_WIDTHS, FPS_RANGE}, theme::Theme};
+
+const USAGE: &str = "usage: rust-mines [--difficulty beginner|intermediate|expert] [--width N] [--height N] [--mines N] [--fit] [--seed HEX] [--load-layout FILE] [--theme dark|light] [--colorblind] [--wrap] [--hex] [--solvable] [--safe-border] [--menu] [--resume] [--replay] [--speed N] [--fps N] [--cell-width 1|2|3] [--accelerate] [--strict-flags] [--auto-open] [--auto-chord] [--safe-reveal] [--animate] [--animate-loss] [--no-sound] [--debug] [--fuzz STEPS]";
+
+pub struct Args {
+    pub difficulty: Difficulty,
+    pub width: usize,
+    pub height: usize,
+    pub mines: usize,
+    pub fit: bool,
+    pub seed: Option<u64>,
+    // A board drawn with '*' and '.', played instead of a generated one
+    pub layout: Option<String>,
+    pub resume: bool,
+    pub menu: bool,
+    pub theme: Theme,
+    pub colorblind: bool,
+    pub wrap: bool,
+    pub topology: Topology,
+    pub solvable: bool,
+    pub safe_border: bool,
+    pub replay: bool,
+    pub speed: f64,
+    pub fps: f32,
+    pub cell_width: u16,
+    pub debug: bool,
+    pub fuzz: Option<usize>,
+    pub accelerate: bool,
+    pub sound: bool,
+    pub strict_flags: bool,
+    pub auto_open: bool,
+    pub auto_chord: bool,
+    pub safe_reveal: bool,
+    pub animate: bool,
+    pub animate_loss: bool,
+    pub keymap: Keymap,
+}
+
+impl Args {
+    // Command line flags override whatever config.toml sets
+    pub fn parse() -> Args {
+        let config = Config::load();
+        let mut difficulty = config.difficulty.as_deref()
+            .and_then(|x| x.parse().ok())
+            .unwrap_or(Difficulty::Beginner);
+        let (mut width, mut height, mut mines) = (config.width, config.height, config.mines);
+        let mut seed = None;
+        let mut layout = None;
+        let mut fit = false;
+
+        let mut resume = false;
+        let mut menu = config.menu;
+        let mut replay = false;
+        let mut speed = 1.0;
+        let mut fps = 60.0;
+        let mut cell_width = *CELL_WIDTHS.end();
+        let mut debug = false;
+        let mut fuzz = None;
+        let mut accelerate = false;
+        let mut sound = true;
+        let mut strict_flags = false;
+        let mut auto_open = false;
+        let mut auto_chord = config.auto_chord;
+        let mut safe_reveal = config.safe_reveal;
+        let mut animate = false;
+        let mut animate_loss = false;
+        let mut theme = config.theme.as_deref().and_then(Theme::by_name).unwrap_or_default();
+        let mut colorblind = config.ascii;
+        let mut keymap = Keymap::default();
+        for (action, keys) in config.keys.iter() {
+            if !keymap.bind(action, keys) {
+                eprintln!("rust-mines: ignoring invalid key binding {} = {:?}", action, keys);
+            }
+        }
+        let mut wrap = false;
+        let mut topology = Topology::Square;
+        let mut solvable = false;
+        let mut safe_border = config.safe_border;
+
+        let mut args = env::args().skip(1);
+        while let Some(arg) = args.next() {
+            let mut value = || args.next().unwrap_or_default();
+            let parsed = match arg.as_str() {
+                "--difficulty" => value().parse().map(|x| difficulty = x).is_ok(),
+                "--width" => value().parse().map(|x| width = Some(x)).is_ok(),
+                "--height" => value().parse().map(|x| height = Some(x)).is_ok(),
+                "--mines" => value().parse().map(|x| mines = Some(x)).is_ok(),
+                "--fit" => { fit = true; true },
+                "--seed" => u64::from_str_radix(&value(), 16).map(|x| seed = Some(x)).is_ok(),
+                "--load-layout" => match fs::read_to_string(value()) {
+                    Ok(text) => match Game::from_layout(&text) {
+                        Ok(_) => { layout = Some(text); true },
+                        Err(err) => { eprintln!("rust-mines: ignoring --load-layout: {}", err); true },
+                    },
+                    Err(_) => false,
+                },
+                "--theme" => Theme::by_name(&value()).map(|x| theme = x).is_some(),
+                "--colorblind" => { colorblind = true; true },
+                "--wrap" => { wrap = true; true },
+                "--hex" => { topology = Topology::Hex; true },
+                "--solvable" => { solvable = true; true },
+                "--safe-border" => { safe_border = true; true },
+                "--resume" => { resume = true; true },
+                "--menu" => { menu = true; true },
+                "--replay" => { replay = true; true },
+                "--debug" => { debug = true; true },
+                "--fuzz" => value().parse().map(|x| fuzz = Some(x)).is_ok(),
+                "--accelerate" => { accelerate = true; true },
+                "--no-sound" => { sound = false; true },
+                "--strict-flags" => { strict_flags = true; true },
+                "--auto-open" => { auto_open = true; true },
+                "--auto-chord" => { auto_chord = true; true },
+                "--safe-reveal" => { safe_reveal = true; true },
+                "--animate" => { animate = true; true },
+                "--animate-loss" => { animate_loss = true; true },
+                "--fps" => value().parse().ok().filter(|x| FPS_RANGE.contains(x)).map(|x| fps = x).is_some(),
+                "--cell-width" => value().parse().ok().filter(|x| CELL_WIDTHS.contains(x)).map(|x| cell_width = x).is_some(),
+                "--speed" => value().parse().ok().filter(|&x: &f64| x > 0.0).map(|x| speed = x).is_some(),
+                _ => false,
+            };
+            if !parsed {
+                eprintln!("rust-mines: invalid argument {}", arg);
+                eprintln!("{}", USAGE);
+            }
+        }
+
+        let (default_width, default_height, default_mines) = difficulty.dimensions();
+        let (width, height) = (width.unwrap_or(default_width), height.unwrap_or(default_height));
+        let cells = width.saturating_mul(height);
+        // Custom boards keep roughly the preset's density unless a count is given
+        let mines = mines.unwrap_or(cells.saturating_mul(default_mines) / (default_width * default_height));
+
+        let (width, height, mines) = if width == 0 || height == 0 || mines == 0 || mines >= cells {
+            eprintln!("rust-mines: invalid board {}x{} with {} mines, using {:?} defaults", width, height, mines, difficulty);
+            (default_width, default_height, default_mines)
+        } else {
+            (width, height, mines)
+        };
+
+        // Checked here while warnings can still be printed, new boards are made with the screen up
+        let interior = width.saturating_sub(2) * height.saturating_sub(2);
+        if safe_border && layout.is_none() && mines >= interior {
+            eprintln!("rust-mines: ignoring --safe-border: {} mines don't fit inside the border of a {}x{} board", mines, width, height);
+            safe_border = false;
+        }
+
+        Args { difficulty, width, height, mines, fit, seed, layout, resume, menu, theme, colorblind, wrap, topology, solvable, safe_border, replay, speed, fps, cell_width, debug, fuzz, accelerate, sound, strict_flags, auto_open, auto_chord, safe_reveal, animate, animate_loss, keymap }
+    }
+}
@@ -0,0 +1,53 @@
+use std::ops::{Index, IndexMut};
+
+use serde::{Deserialize, Serialize};
+
+use crate::game::Tile;
+
+#[derive(Serialize, Deserialize)]
+pub struct Board {
+    pub width: usize,
+    pub height: usize,
+    pub tiles: Vec<Tile>,
+}
+
+impl Board {
+    pub fn new(width: usize, height: usize, tiles: Vec<Tile>) -> Board {
+        Board { width, height, tiles }
+    }
+
+    pub fn idx(&self, x: usize, y: usize) -> usize {
+        x + y * self.width
+    }
+
+    pub fn in_bounds(&self, x: usize, y: usize) -> bool {
+        x < self.width && y < self.height
+    }
+
+    pub fn get(&self, x: usize, y: usize) -> Option<&Tile> {
+        if !self.in_bounds(x, y) { return None; }
+        self.tiles.get(self.idx(x, y))
+    }
+
+    pub fn get_mut(&mut self, x: usize, y: usize) -> Option<&mut Tile> {
+        if !self.in_bounds(x, y) { return None; }
+        let i = self.idx(x, y);
+        self.tiles.get_mut(i)
+    }
+}
+
+// Unchecked access for positions already known to be on the board, like neighbor lists
+impl Index<(usize, usize)> for Board {
+    type Output = Tile;
+
+    fn index(&self, (x, y): (usize, usize)) -> &Tile {
+        &self.tiles[self.idx(x, y)]
+    }
+}
+
+impl IndexMut<(usize, usize)> for Board {
+    fn index_mut(&mut self, (x, y): (usize, usize)) -> &mut Tile {
+        let i = self.idx(x, y);
+        &mut self.tiles[i]
+    }
+}
@@ -0,0 +1,42 @@
+use std::{collections::HashMap, fs, path::PathBuf};
+
+use serde::Deserialize;
+
+// Defaults from config.toml, anything left out falls back to the built-in ones
+#[derive(Default, Deserialize)]
+#[serde(default)]
+pub struct Config {
+    pub difficulty: Option<String>,
+    pub width: Option<usize>,
+    pub height: Option<usize>,
+    pub mines: Option<usize>,
+    pub theme: Option<String>,
+    pub ascii: bool,
+    // Start on the board picker instead of going straight into a game
+    pub menu: bool,
+    // Refuse to open tiles that are provably mines, see --safe-reveal
+    pub safe_reveal: bool,
+    // Keep mines off the outermost ring of tiles, see --safe-border
+    pub safe_border: bool,
+    // Chord numbers as soon as they're revealed if their flags are all placed, see --auto-chord
+    pub auto_chord: bool,
+    // Action names mapped to the keys that trigger them, e.g. `mark = ["f", "m"]`
+    pub keys: HashMap<String, Vec<String>>,
+}
+
+impl Config {
+    fn path() -> Option<PathBuf> {
+        dirs::config_dir().map(|dir| dir.join("rust-mines").join("config.toml"))
+    }
+
+    // A missing file is the same as an empty one, a broken one is reported and ignored
+    pub fn load() -> Config {
+        let Some(toml) = Config::path().and_then(|path| fs::read_to_string(path).ok()) else {
+            return Config::default();
+        };
+        toml::from_str(&toml).unwrap_or_else(|err| {
+            eprintln!("rust-mines: ignoring invalid config.toml: {}", err);
+            Config::default()
+        })
+    }
+}
@@ -0,0 +1,78 @@
+use std::{io::Result, process};
+
+use rand::{rngs::StdRng, Rng, SeedableRng};
+
+use crate::game::{Difficulty, Game, Topology};
+
+// Games are restarted with a new layout this often, so small and odd boards get their turn
+const STEPS_PER_GAME: usize = 200;
+
+// Throws a seeded stream of random actions at random boards without a screen, stopping at
+// the first broken invariant. Any seed that fails reproduces the same run
+pub fn run(seed: u64, steps: usize) -> Result<()> {
+    if let Err(broken) = play(seed, steps) {
+        eprintln!("rust-mines: fuzz seed {:x} {}", seed, broken);
+        process::exit(1);
+    }
+
+    println!("rust-mines: {} random actions from seed {:x} kept every invariant", steps, seed);
+    Ok(())
+}
+
+fn play(seed: u64, steps: usize) -> std::result::Result<(), String> {
+    let mut rng = StdRng::seed_from_u64(seed);
+    let mut game = random_game(&mut rng);
+
+    for step in 0..steps {
+        if step % STEPS_PER_GAME == 0 { game = random_game(&mut rng); }
+
+        let (width, height) = (game.board.width as i32, game.board.height as i32);
+        game.set_selected((rng.gen_range(0..width), rng.gen_range(0..height)));
+        match rng.gen_range(0..100) {
+            0..=49 => { game.click_tile(); },
+            50..=74 => game.toggle_mark(),
+            75..=79 => game.undo_mark(),
+            80..=84 => game.auto_flag(),
+            85..=89 => game.flag_or_reveal(),
+            90..=93 => if let Some((x, y)) = game.hint() { game.reveal(x, y); },
+            94..=95 => { game.solve(); },
+            96 => game.reveal_all(),
+            _ => game.reset_rng(&mut rng),
+        }
+
+        if let Some(broken) = game.broken_invariant() {
+            return Err(format!("broke at step {}: {}", step, broken));
+        }
+    }
+    Ok(())
+}
+
+fn random_game(rng: &mut StdRng) -> Game {
+    let (width, height) = (rng.gen_range(1..=12), rng.gen_range(1..=12));
+    let mines = rng.gen_range(0..width * height);
+    let mut game = match rng.gen_bool(0.2) {
+        true => Game::new_solvable(width, height, mines, rng.gen()),
+        false => Game::with_mines_rng(width, height, mines, rng),
+    }.or_else(|_| Game::new_seeded(width, height, rng.gen()))
+        .unwrap_or_else(|_| Game::from_difficulty(Difficulty::Beginner));
+
+    let topology = if rng.gen_bool(0.3) { Topology::Hex } else { Topology::Square };
+    game.reshape(rng.gen_bool(0.3), topology);
+    if rng.gen_bool(0.2) { let _ = game.set_safe_border(); }
+    game.strict_flags = rng.gen_bool(0.3);
+    game.safe_reveal = rng.gen_bool(0.2);
+    game.auto_open = rng.gen_bool(0.2);
+    game.auto_chord = rng.gen_bool(0.2);
+    if game.auto_open { game.open(); }
+    game
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn a_fixed_seed_keeps_every_invariant() {
+        assert_eq!(play(0x5eed, 20_000), Ok(()));
+    }
+}
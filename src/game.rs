@@ -1,145 +1,1602 @@
-use rand::{distributions::Standard, prelude::Distribution, Rng};
+use std::{fmt, fs, io, path::Path, str::FromStr, time::Duration};
 
+use rand::{rngs::StdRng, seq::index, Rng, SeedableRng};
+use serde::{Deserialize, Serialize};
+
+use crate::{board::Board, timer::Timer};
+
+pub const DEFAULT_MINE_RATIO: f64 = 0.1;
+const SOLVABLE_ATTEMPTS: usize = 200;
+const MIN_MINE_RATIO: f64 = 0.01;
+const MAX_MINE_RATIO: f64 = 0.99;
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Serialize, Deserialize)]
 pub enum TileVisibility {
     Visible,
     Marked,
+    Questioned,
     Hidden,
 }
 
+#[derive(Serialize, Deserialize)]
 pub enum TileType {
     Safe,
     Mine,
 }
 
-impl Distribution<TileType> for Standard {
-    fn sample<R: Rng + ?Sized>(&self, rng: &mut R) -> TileType {
-        match rng.gen_range(0..=9) {
-            0..=8 => TileType::Safe,
-            _ => TileType::Mine,
+#[derive(Debug)]
+pub enum GameError {
+    TooManyMines { mines: usize, cells: usize },
+    EmptyBoard { width: usize, height: usize },
+    TooLarge { width: usize, height: usize },
+    RaggedRow { row: usize, width: usize, expected: usize },
+    UnknownTile { tile: char, row: usize },
+}
+
+impl fmt::Display for GameError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            GameError::TooManyMines { mines, cells } =>
+                write!(f, "{} mines do not fit on a board of {} tiles", mines, cells),
+            GameError::EmptyBoard { width, height } =>
+                write!(f, "a {}x{} board has no tiles", width, height),
+            GameError::TooLarge { width, height } =>
+                write!(f, "a {}x{} board has too many tiles", width, height),
+            GameError::RaggedRow { row, width, expected } =>
+                write!(f, "row {} is {} tiles wide instead of {}", row + 1, width, expected),
+            GameError::UnknownTile { tile, row } =>
+                write!(f, "row {} has a '{}', only '*' for mines and '.' for safe tiles are allowed", row + 1, tile),
+        }
+    }
+}
+
+impl std::error::Error for GameError {}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Default, Serialize, Deserialize)]
+pub enum Topology {
+    #[default]
+    Square,
+    Hex,
+}
+
+#[derive(Clone, PartialEq, Debug, Serialize, Deserialize)]
+pub enum MinePlacement {
+    Density(f64),
+    Count(usize),
+    // Exact count, rerolled until the board can be cleared without guessing
+    Solvable(usize),
+    // Indices of the mines of a hand made layout, the seed plays no part
+    Fixed(Vec<usize>),
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Difficulty {
+    Beginner,
+    Intermediate,
+    Expert,
+}
+
+impl Difficulty {
+    pub fn dimensions(&self) -> (usize, usize, usize) {
+        match self {
+            Difficulty::Beginner => (9, 9, 10),
+            Difficulty::Intermediate => (16, 16, 40),
+            Difficulty::Expert => (30, 16, 99),
+        }
+    }
+
+    pub fn matching(width: usize, height: usize, mines: usize) -> Option<Difficulty> {
+        [Difficulty::Beginner, Difficulty::Intermediate, Difficulty::Expert].into_iter()
+            .find(|x| x.dimensions() == (width, height, mines))
+    }
+}
+
+impl FromStr for Difficulty {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Difficulty, ()> {
+        match s.to_lowercase().as_str() {
+            "beginner" => Ok(Difficulty::Beginner),
+            "intermediate" => Ok(Difficulty::Intermediate),
+            "expert" => Ok(Difficulty::Expert),
+            _ => Err(()),
         }
     }
 }
 
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Serialize, Deserialize)]
+pub enum GameState {
+    Playing,
+    Won,
+    Lost,
+    // Ended by revealing the whole board, neither a win nor a loss
+    Surrendered,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Serialize, Deserialize)]
+pub enum MoveKind {
+    Reveal,
+    Mark,
+    Undo,
+    AutoFlag,
+}
+
+// A single player action, timestamped by the game clock so it can be replayed
+#[derive(Clone, Copy, Debug, Serialize, Deserialize)]
+pub struct Move {
+    pub at: Duration,
+    pub x: usize,
+    pub y: usize,
+    pub kind: MoveKind,
+}
+
+#[derive(Serialize, Deserialize)]
 pub struct Tile {
     pub x: usize,
     pub y: usize,
     pub tile_type: TileType,
     pub tile_visibility: TileVisibility,
     pub mine_count: u8,
+    // A player's scratch note, it plays no part in the game itself
+    #[serde(default)]
+    pub annotation: Option<char>,
 }
 
 impl Tile {
-    fn neighbors(&self, width: usize, height: usize) -> Vec<(usize, usize)> {
+    // Flags block every kind of reveal, question marks don't
+    pub fn is_clickable(&self) -> bool {
+        matches!(self.tile_visibility, TileVisibility::Hidden | TileVisibility::Questioned)
+    }
+
+    fn neighbors(&self, width: usize, height: usize, wrap: bool, topology: Topology) -> Vec<(usize, usize)> {
+        let offsets: &[(isize, isize)] = match topology {
+            // Left, Up, Right, Down, Left Up, Left Down, Right Up, Right Down
+            Topology::Square => &[(-1, 0), (0, -1), (1, 0), (0, 1), (-1, -1), (-1, 1), (1, -1), (1, 1)],
+            // Odd rows sit half a tile right of even rows, so the diagonals lean the other way
+            Topology::Hex if self.y.is_multiple_of(2) => &[(-1, 0), (0, -1), (1, 0), (0, 1), (-1, -1), (-1, 1)],
+            Topology::Hex => &[(-1, 0), (0, -1), (1, 0), (0, 1), (1, -1), (1, 1)],
+        };
+
         let mut neighbors = Vec::new();
-        if self.x > 0          { neighbors.push((self.x - 1, self.y)); }  // Left
-        if self.y > 0          { neighbors.push((self.x, self.y - 1)); }  // Up
-        if self.x < width - 1  { neighbors.push((self.x + 1, self.y)); }  // Right
-        if self.y < height - 1 { neighbors.push((self.x, self.y + 1)); }  // Down
-        if self.x > 0 && self.y > 0                  { neighbors.push((self.x - 1, self.y - 1)); }  // Left Up
-        if self.x > 0 && self.y < height - 1         { neighbors.push((self.x - 1, self.y + 1)); }  // Left Down
-        if self.x < width - 1 && self.y > 0          { neighbors.push((self.x + 1, self.y - 1)); }  // Right Up
-        if self.x < width - 1 && self.y < height - 1 { neighbors.push((self.x + 1, self.y + 1)); }  // Right Down
-    
-        return neighbors;
+        if width == 0 || height == 0 { return neighbors; }
+        let (width, height) = (width as isize, height as isize);
+        for (dx, dy) in offsets {
+            let (x, y) = (self.x as isize + dx, self.y as isize + dy);
+            // Opposite edges touch, tiny boards can reach the same tile from two sides. Hex boards
+            // of odd height have unshifted rows on both sides of that seam, so stepping up across it
+            // goes a column further right to meet the tiles that step down onto this one
+            let x = if wrap && topology == Topology::Hex && height % 2 == 1 && y < 0 { x + 1 } else { x };
+            let (x, y) = match wrap {
+                true => (x.rem_euclid(width) as usize, y.rem_euclid(height) as usize),
+                false if (0..width).contains(&x) && (0..height).contains(&y) => (x as usize, y as usize),
+                false => continue,
+            };
+            if (x, y) != (self.x, self.y) && !neighbors.contains(&(x, y)) {
+                neighbors.push((x, y));
+            }
+        }
+        neighbors
     }
 }
 
+#[derive(Serialize, Deserialize)]
 pub struct Game {
-    pub width: usize,
-    pub height: usize,
-    pub tiles: Vec<Tile>,
+    // Flattened so saves keep the same layout as before the board was split out
+    #[serde(flatten)]
+    pub board: Board,
     pub selected: (usize, usize),
+    pub state: GameState,
+    pub seed: u64,
+    pub placement: MinePlacement,
+    pub mine_total: usize,
+    pub timer: Timer,
+    #[serde(default)]
+    pub wrap: bool,
+    #[serde(default)]
+    pub topology: Topology,
+    #[serde(default)]
+    pub history: Vec<((usize, usize), TileVisibility)>,
+    #[serde(default)]
+    pub tiles_revealed: usize,
+    #[serde(default)]
+    pub flags_used: usize,
+    // Whether any flag went down this game, by hand or by auto-flag
+    #[serde(default)]
+    pub flagged: bool,
+    // Caps the flags on the board at the mine count
+    #[serde(default)]
+    pub strict_flags: bool,
+    // Restarts open the board on their own instead of waiting for the first click
+    #[serde(default)]
+    pub auto_open: bool,
+    // Training aid, clicks on tiles the numbers already prove are mines are ignored
+    #[serde(default)]
+    pub safe_reveal: bool,
+    // Mines are only ever placed away from the edges of the board
+    #[serde(default)]
+    pub safe_border: bool,
+    // Revealing a number that its flags already account for chords it straight away
+    #[serde(default)]
+    pub auto_chord: bool,
+    // Saved games always resume unpaused
+    #[serde(skip)]
+    pub paused: bool,
+    #[serde(default)]
+    pub moves: Vec<Move>,
+    // The mine that ended the game, if one did
+    #[serde(default)]
+    pub detonated: Option<(usize, usize)>,
+    // Tiles the last reveal opened, grouped by distance from where each flood started
+    #[serde(skip)]
+    pub reveal_layers: Vec<Vec<(usize, usize)>>,
+    // Board indices of every tile's neighbors, rebuilt whenever the layout changes
+    #[serde(skip)]
+    adjacency: Vec<Vec<usize>>,
 }
 
 impl Game {
+    // Panics on boards try_new rejects
     pub fn new(width: usize, height: usize) -> Game {
-        let mut rng = rand::thread_rng();
-        let mut tiles: Vec<Tile> = (0..(height*width).into()).map(|i| Tile {
+        Game::try_new(width, height).unwrap()
+    }
+
+    pub fn try_new(width: usize, height: usize) -> Result<Game, GameError> {
+        Game::with_density(width, height, DEFAULT_MINE_RATIO)
+    }
+
+    fn cells(width: usize, height: usize) -> Result<usize, GameError> {
+        if width == 0 || height == 0 {
+            return Err(GameError::EmptyBoard { width, height });
+        }
+        width.checked_mul(height).ok_or(GameError::TooLarge { width, height })
+    }
+
+    pub fn new_seeded(width: usize, height: usize, seed: u64) -> Result<Game, GameError> {
+        Game::generate(width, height, DEFAULT_MINE_RATIO, seed)
+    }
+
+    pub fn with_density(width: usize, height: usize, mine_ratio: f64) -> Result<Game, GameError> {
+        Game::generate(width, height, mine_ratio, rand::random())
+    }
+
+    fn generate(width: usize, height: usize, mine_ratio: f64, seed: u64) -> Result<Game, GameError> {
+        Game::cells(width, height)?;
+        // Keep at least some mines and some safe tiles, NaN falls back to the default
+        let mine_ratio = if mine_ratio.is_nan() { DEFAULT_MINE_RATIO }
+            else { mine_ratio.clamp(MIN_MINE_RATIO, MAX_MINE_RATIO) };
+
+        Ok(Game::build(width, height, MinePlacement::Density(mine_ratio), seed, false))
+    }
+
+    pub fn from_difficulty(difficulty: Difficulty) -> Game {
+        let (width, height, mines) = difficulty.dimensions();
+        Game::with_mines(width, height, mines).unwrap_or_else(|_| Game::new(width, height))
+    }
+
+    pub fn with_mines(width: usize, height: usize, mine_count: usize) -> Result<Game, GameError> {
+        Game::with_mines_rng(width, height, mine_count, &mut rand::thread_rng())
+    }
+
+    // The seed is drawn from the given generator, so a seeded one always lays the same mines.
+    // Placement itself stays on that seed so saved games can be rebuilt for replays
+    pub fn with_mines_rng(width: usize, height: usize, mine_count: usize, rng: &mut impl Rng) -> Result<Game, GameError> {
+        Game::with_mines_seeded(width, height, mine_count, rng.gen())
+    }
+
+    pub fn with_mines_seeded(width: usize, height: usize, mine_count: usize, seed: u64) -> Result<Game, GameError> {
+        let cells = Game::cells(width, height)?;
+        if mine_count >= cells {
+            return Err(GameError::TooManyMines { mines: mine_count, cells });
+        }
+
+        Ok(Game::build(width, height, MinePlacement::Count(mine_count), seed, false))
+    }
+
+    pub fn new_solvable(width: usize, height: usize, mine_count: usize, seed: u64) -> Result<Game, GameError> {
+        let cells = Game::cells(width, height)?;
+        if mine_count >= cells {
+            return Err(GameError::TooManyMines { mines: mine_count, cells });
+        }
+
+        Ok(Game::solvable(width, height, mine_count, seed, false, Topology::Square, false))
+    }
+
+    // Parses a grid of '*' for mines and '.' for safe tiles, one row per line
+    pub fn from_layout(layout: &str) -> Result<Game, GameError> {
+        let rows: Vec<&str> = layout.lines().map(str::trim_end).filter(|x| !x.is_empty()).collect();
+        let width = rows.first().map_or(0, |x| x.chars().count());
+        let cells = Game::cells(width, rows.len())?;
+
+        let mut mines = Vec::new();
+        for (y, row) in rows.iter().enumerate() {
+            let row_width = row.chars().count();
+            if row_width != width {
+                return Err(GameError::RaggedRow { row: y, width: row_width, expected: width });
+            }
+            for (x, tile) in row.chars().enumerate() {
+                match tile {
+                    '*' => mines.push(x + y * width),
+                    '.' => (),
+                    tile => return Err(GameError::UnknownTile { tile, row: y }),
+                }
+            }
+        }
+        if mines.len() >= cells {
+            return Err(GameError::TooManyMines { mines: mines.len(), cells });
+        }
+
+        Ok(Game::build(width, rows.len(), MinePlacement::Fixed(mines), 0, false))
+    }
+
+    fn solvable(width: usize, height: usize, mine_count: usize, seed: u64, wrap: bool, topology: Topology, safe_border: bool) -> Game {
+        let build = |seed: u64| {
+            let mut game = Game::build(width, height, MinePlacement::Solvable(mine_count), seed, safe_border);
+            game.set_layout(wrap, topology);
+            game.open();
+            game
+        };
+
+        let mut rng = StdRng::seed_from_u64(seed);
+        for _ in 0..SOLVABLE_ATTEMPTS {
+            let attempt = rng.gen();
+            if build(attempt).solve() {
+                return build(attempt);
+            }
+        }
+
+        // Give up on a guess-free board rather than rerolling forever
+        let mut game = Game::build(width, height, MinePlacement::Count(mine_count), seed, safe_border);
+        game.set_layout(wrap, topology);
+        game
+    }
+
+    // Reveal the board's opening, preferably a tile with no adjacent mines. The first
+    // reveal clears the tiles around it, so that ends up opening a zero region
+    pub fn open(&mut self) {
+        if self.board.tiles.iter().any(|x| x.tile_visibility == TileVisibility::Visible) { return; }
+
+        let opening = self.board.tiles.iter()
+            .filter(|x| matches!(x.tile_type, TileType::Safe))
+            .min_by_key(|x| x.mine_count)
+            .map(|x| (x.x, x.y));
+
+        if let Some((x, y)) = opening {
+            self.selected = (x, y);
+            self.reveal(x, y);
+            // The clock starts with the player's first move, not the automatic opening
+            self.timer = Timer::default();
+        }
+    }
+
+    fn build(width: usize, height: usize, placement: MinePlacement, seed: u64, safe_border: bool) -> Game {
+        let mut rng = StdRng::seed_from_u64(seed);
+        // Without a safe border every cell is a candidate, so seeds keep giving the same boards
+        let candidates: Vec<usize> = (0..height*width)
+            .filter(|&i| !safe_border || !Game::on_border(i % width, i / width, width, height))
+            .collect();
+        let mut mines = vec![false; height*width];
+        match &placement {
+            &MinePlacement::Density(mine_ratio) => {
+                for &i in candidates.iter() {
+                    mines[i] = rng.gen_bool(mine_ratio);
+                }
+                // Rolls with no mines or nothing but mines get one candidate flipped
+                let placed = mines.iter().filter(|&&mine| mine).count();
+                if candidates.len() > 1 && (placed == 0 || placed == height*width) {
+                    let i = candidates[rng.gen_range(0..candidates.len())];
+                    mines[i] = !mines[i];
+                }
+            },
+            &MinePlacement::Count(mine_count) | &MinePlacement::Solvable(mine_count) => {
+                for i in index::sample(&mut rng, candidates.len(), mine_count.min(candidates.len())) {
+                    mines[candidates[i]] = true;
+                }
+            },
+            MinePlacement::Fixed(fixed) => {
+                for &i in fixed { mines[i] = true; }
+            },
+        };
+
+        let tiles: Vec<Tile> = (0..height*width).map(|i| Tile {
             x: i % width,
             y: i / width,
-            tile_type: rng.gen(),
+            tile_type: if mines[i] { TileType::Mine } else { TileType::Safe },
             tile_visibility: TileVisibility::Hidden,
             mine_count: 0,
+            annotation: None,
         }).collect();
 
-        for i in 0..tiles.len() {
-            let tile = &tiles[i];
+        let mine_total = mines.iter().filter(|&&mine| mine).count();
 
-            let mut mine_count: u8 = 0;
-            for (x, y) in tile.neighbors(width, height).iter() {
-                let neighbor_tile = &tiles[x + y * width];
-                if matches!(neighbor_tile.tile_type, TileType::Mine) {
-                    mine_count += 1;
+        let mut game = Game {
+            board: Board::new(width, height, tiles),
+            selected: (0, 0),
+            state: GameState::Playing,
+            seed,
+            placement,
+            mine_total,
+            timer: Timer::default(),
+            wrap: false,
+            topology: Topology::Square,
+            history: Vec::new(),
+            tiles_revealed: 0,
+            flags_used: 0,
+            flagged: false,
+            strict_flags: false,
+            auto_open: false,
+            safe_reveal: false,
+            safe_border,
+            auto_chord: false,
+            paused: false,
+            moves: Vec::new(),
+            detonated: None,
+            reveal_layers: Vec::new(),
+            adjacency: Vec::new(),
+        };
+        game.cache_neighbors();
+        game.recompute_counts();
+        game
+    }
+
+    // Has to run again whenever mines move or the topology changes
+    pub fn recompute_counts(&mut self) {
+        for i in 0..self.board.tiles.len() {
+            let mine_count = self.adjacency[i].iter()
+                .filter(|&&j| matches!(self.board.tiles[j].tile_type, TileType::Mine))
+                .count();
+            self.board.tiles[i].mine_count = mine_count as u8;
+        }
+    }
+
+    pub fn set_layout(&mut self, wrap: bool, topology: Topology) {
+        let changed = (wrap, topology) != (self.wrap, self.topology);
+        self.wrap = wrap;
+        self.topology = topology;
+        if changed { self.cache_neighbors(); }
+        self.recompute_counts();
+    }
+
+    fn cache_neighbors(&mut self) {
+        let (width, height) = (self.board.width, self.board.height);
+        self.adjacency = self.board.tiles.iter()
+            .map(|tile| tile.neighbors(width, height, self.wrap, self.topology).into_iter()
+                .map(|(x, y)| self.board.idx(x, y))
+                .collect())
+            .collect();
+    }
+
+    pub fn neighbors(&self, tile: &Tile) -> Vec<(usize, usize)> {
+        self.adjacency[self.board.idx(tile.x, tile.y)].iter()
+            .map(|&i| (self.board.tiles[i].x, self.board.tiles[i].y))
+            .collect()
+    }
+
+    fn on_border(x: usize, y: usize, width: usize, height: usize) -> bool {
+        x == 0 || y == 0 || x + 1 == width || y + 1 == height
+    }
+
+    // Lays the same board out again with no mines in the outermost ring, as long as they
+    // fit inside it with a tile to spare
+    pub fn set_safe_border(&mut self) -> Result<(), GameError> {
+        let (width, height) = (self.board.width, self.board.height);
+        let interior = width.saturating_sub(2) * height.saturating_sub(2);
+        if self.mine_total >= interior {
+            return Err(GameError::TooManyMines { mines: self.mine_total, cells: interior });
+        }
+
+        *self = match self.placement.clone() {
+            MinePlacement::Solvable(mine_count) =>
+                Game::solvable(width, height, mine_count, self.seed, self.wrap, self.topology, true),
+            placement => {
+                let mut game = Game::build(width, height, placement, self.seed, true);
+                game.set_layout(self.wrap, self.topology);
+                game
+            },
+        };
+        Ok(())
+    }
+
+    // Solvability depends on the topology, so those boards are regenerated for it
+    pub fn reshape(&mut self, wrap: bool, topology: Topology) {
+        match self.placement {
+            MinePlacement::Solvable(mine_count) =>
+                *self = Game::solvable(self.board.width, self.board.height, mine_count, self.seed, wrap, topology, self.safe_border),
+            _ => self.set_layout(wrap, topology),
+        }
+    }
+
+    pub fn reset_new(&mut self) {
+        self.reset_rng(&mut rand::thread_rng());
+    }
+
+    // Back to the start of this very board, from its stored seed, for another try at it
+    pub fn reset_same(&mut self) {
+        let (auto_open, safe_reveal) = (self.auto_open, self.safe_reveal);
+        *self = self.replay_board();
+        self.auto_open = auto_open;
+        self.safe_reveal = safe_reveal;
+        // Solvable boards were checked from their opening, so they always start with it shown
+        if auto_open || matches!(self.placement, MinePlacement::Solvable(_)) { self.open(); }
+    }
+
+    // A new board of the same kind, seeded from the given generator
+    pub fn reset_rng(&mut self, rng: &mut impl Rng) {
+        let (wrap, topology) = (self.wrap, self.topology);
+        let (strict_flags, auto_open, safe_reveal, auto_chord) = (self.strict_flags, self.auto_open, self.safe_reveal, self.auto_chord);
+        match self.placement.clone() {
+            MinePlacement::Solvable(mine_count) =>
+                *self = Game::solvable(self.board.width, self.board.height, mine_count, rng.gen(), wrap, topology, self.safe_border),
+            placement => {
+                *self = Game::build(self.board.width, self.board.height, placement, rng.gen(), self.safe_border);
+                self.set_layout(wrap, topology);
+            },
+        }
+        self.strict_flags = strict_flags;
+        self.auto_open = auto_open;
+        self.safe_reveal = safe_reveal;
+        self.auto_chord = auto_chord;
+        if auto_open { self.open(); }
+    }
+
+    // The same board as this game started with, for replaying its moves onto
+    pub fn replay_board(&self) -> Game {
+        let mut game = Game::build(self.board.width, self.board.height, self.placement.clone(), self.seed, self.safe_border);
+        game.set_layout(self.wrap, self.topology);
+        game.strict_flags = self.strict_flags;
+        game.auto_chord = self.auto_chord;
+        game
+    }
+
+    fn record(&mut self, x: usize, y: usize, kind: MoveKind) {
+        self.moves.push(Move { at: self.elapsed(), x, y, kind });
+    }
+
+    pub fn apply(&mut self, mv: Move) {
+        self.selected = (mv.x, mv.y);
+        match mv.kind {
+            MoveKind::Reveal => { self.reveal(mv.x, mv.y); },
+            MoveKind::Mark => { self.mark(mv.x, mv.y); },
+            MoveKind::Undo => self.undo_mark(),
+            MoveKind::AutoFlag => self.auto_flag(),
+        }
+    }
+
+    pub fn flags_placed(&self) -> usize {
+        self.board.tiles.iter()
+            .filter(|x| matches!(x.tile_visibility, TileVisibility::Marked))
+            .count()
+    }
+
+    pub fn mines_remaining(&self) -> i32 {
+        self.mine_total as i32 - self.flags_placed() as i32
+    }
+
+    pub fn save(&self, path: &Path) -> io::Result<()> {
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+        fs::write(path, serde_json::to_string(self)?)
+    }
+
+    pub fn load(path: &Path) -> io::Result<Game> {
+        let mut game: Game = serde_json::from_str(&fs::read_to_string(path)?)?;
+        game.cache_neighbors();
+        Ok(game)
+    }
+
+    // Only a running game can be paused, the timer is frozen while it is
+    pub fn toggle_pause(&mut self) {
+        if self.state != GameState::Playing { return; }
+
+        self.paused = !self.paused;
+        if self.paused { self.timer.pause(); } else { self.timer.resume(); }
+    }
+
+    // Boards left in the background are paused, so their clocks don't run unseen
+    pub fn suspend(&mut self) {
+        if !self.paused && self.timer.running() { self.toggle_pause(); }
+    }
+
+    pub fn no_flag_clear(&self) -> bool {
+        self.state == GameState::Won && !self.flagged
+    }
+
+    pub fn elapsed(&self) -> Duration {
+        self.timer.elapsed()
+    }
+
+    // 3BV, the fewest clicks that clear the board: one per opening plus one per number outside of them
+    pub fn three_bv(&self) -> usize {
+        let tiles = &self.board.tiles;
+        let is_opening = |i: usize| matches!(tiles[i].tile_type, TileType::Safe) && tiles[i].mine_count == 0;
+        let mut covered = vec![false; tiles.len()];
+        let mut clicks = 0;
+        for start in 0..tiles.len() {
+            if covered[start] || !is_opening(start) { continue; }
+            clicks += 1;
+            covered[start] = true;
+            // An opening clears its numbered edge along with it
+            let mut stack = vec![start];
+            while let Some(i) = stack.pop() {
+                for &j in &self.adjacency[i] {
+                    if covered[j] { continue; }
+                    covered[j] = true;
+                    if is_opening(j) { stack.push(j); }
                 }
             }
-            tiles[i].mine_count = mine_count;
         }
+        clicks + tiles.iter().zip(&covered)
+            .filter(|&(tile, &covered)| !covered && matches!(tile.tile_type, TileType::Safe))
+            .count()
+    }
 
-        Game {
-            width: width,
-            height: height,
-            tiles: tiles,
-            selected: (0, 0),
+    // Every recorded move counts as a click, so flags and chords lower it too
+    pub fn efficiency(&self) -> Option<f64> {
+        if self.moves.is_empty() { return None; }
+        Some(self.three_bv() as f64 / self.moves.len() as f64)
+    }
+
+    fn finish(&mut self, state: GameState) {
+        self.state = state;
+        self.timer.stop();
+
+        // Winning flags the mines left hidden so the board looks complete, otherwise every mine is shown
+        let shown = match state {
+            GameState::Won => TileVisibility::Marked,
+            _ => TileVisibility::Visible,
+        };
+        self.board.tiles.iter_mut()
+            .filter(|x| matches!(x.tile_type, TileType::Mine))
+            .for_each(|x| x.tile_visibility = shown);
+    }
+
+    // The mines shown by a loss, grouped by how many steps they are from the one that went off
+    pub fn mine_reveal_order(&self) -> Vec<Vec<(usize, usize)>> {
+        let Some((x, y)) = self.detonated else { return Vec::new(); };
+        let mut seen = vec![false; self.board.tiles.len()];
+        let mut layer = vec![self.board.idx(x, y)];
+        seen[layer[0]] = true;
+
+        let mut order = Vec::new();
+        while !layer.is_empty() {
+            let mines: Vec<(usize, usize)> = layer.iter()
+                .map(|&i| &self.board.tiles[i])
+                .filter(|x| matches!(x.tile_type, TileType::Mine) && x.tile_visibility == TileVisibility::Visible)
+                .map(|x| (x.x, x.y))
+                .collect();
+            if !mines.is_empty() { order.push(mines); }
+
+            let mut next = Vec::new();
+            for i in layer {
+                for &j in &self.adjacency[i] {
+                    if !seen[j] { seen[j] = true; next.push(j); }
+                }
+            }
+            layer = next;
         }
+        order
     }
 
-    pub fn end_game(&self, message: &str) {
-        println!("{}", message);
+    pub fn reveal_all(&mut self) {
+        if self.state != GameState::Playing { return; }
+
+        self.board.tiles.iter_mut().for_each(|x| x.tile_visibility = TileVisibility::Visible);
+        self.finish(GameState::Surrendered);
     }
 
-    pub fn check_game_won(&self) {
-        if !self.tiles.iter().any(|x|
-            matches!(x.tile_visibility, TileVisibility::Hidden) &&
-            matches!(x.tile_type, TileType::Safe)
-        ) { self.end_game("Game won ^-^"); }
+    pub fn check_game_won(&mut self) {
+        if self.state != GameState::Playing { return; }
+        if self.safe_remaining() == 0 { self.finish(GameState::Won); }
+    }
+
+    // Safe tiles still standing between the player and a win. Flags on mines don't matter either
+    // way, but a flag on a safe tile still has to come off and be revealed
+    fn safe_remaining(&self) -> usize {
+        self.board.tiles.iter()
+            .filter(|x| x.tile_visibility != TileVisibility::Visible)
+            .filter(|x| matches!(x.tile_type, TileType::Safe))
+            .count()
+    }
+
+    pub fn percent_cleared(&self) -> usize {
+        let safe_total = self.board.tiles.len() - self.mine_total;
+        if safe_total == 0 { return 100; }
+        (safe_total - self.safe_remaining()) * 100 / safe_total
+    }
+
+    // Revealed tiles have nothing left to take notes about
+    pub fn annotate(&mut self, annotation: Option<char>) {
+        let tile = &mut self.board[self.selected];
+        if tile.tile_visibility != TileVisibility::Visible { tile.annotation = annotation; }
     }
 
     pub fn toggle_mark(&mut self) {
         let (x, y) = self.selected;
-        let tile = &mut self.tiles[x + y * self.width];
+        self.mark(x, y);
+    }
+
+    pub fn mark(&mut self, x: usize, y: usize) -> GameState {
+        if self.state != GameState::Playing { return self.state; }
+        let out_of_flags = self.strict_flags && self.flags_placed() >= self.mine_total;
+        let Some(tile) = self.board.get_mut(x, y) else { return self.state; };
         let tile_visibility = &mut tile.tile_visibility;
+        let previous = *tile_visibility;
         match tile_visibility {
-            TileVisibility::Hidden => *tile_visibility = TileVisibility::Marked,
-            TileVisibility::Marked => *tile_visibility = TileVisibility::Hidden,
-            _ => (),
+            // Flags can always come off, but not go on past the cap
+            TileVisibility::Hidden if out_of_flags => return self.state,
+            TileVisibility::Hidden => {
+                *tile_visibility = TileVisibility::Marked;
+                self.flags_used += 1;
+                self.flagged = true;
+            },
+            TileVisibility::Marked => *tile_visibility = TileVisibility::Questioned,
+            TileVisibility::Questioned => *tile_visibility = TileVisibility::Hidden,
+            _ => return self.state,
         };
+        self.history.push(((x, y), previous));
+        self.record(x, y, MoveKind::Mark);
         self.check_game_won();
+        self.state
+    }
+
+    // One key from hidden to done: hidden tiles get flagged, pressing again on a flag
+    // takes it back off and reveals the tile, anything else is clicked as usual
+    pub fn flag_or_reveal(&mut self) {
+        let (x, y) = self.selected;
+        match self.board[(x, y)].tile_visibility {
+            TileVisibility::Hidden => { self.mark(x, y); },
+            // Safe reveal would refuse to open it, so the flag is left where it is
+            TileVisibility::Marked if self.safe_reveal && self.proven_mine(x, y) => (),
+            TileVisibility::Marked => {
+                // Marking a flag again turns it into a question mark, which can be revealed
+                self.mark(x, y);
+                self.click_tile();
+            },
+            _ => { self.click_tile(); },
+        }
     }
 
-    pub fn flood_reveal(&mut self, x: usize, y: usize) {
-        let tile = &mut self.tiles[x + y * self.width];
+    pub fn undo_mark(&mut self) {
+        if self.state != GameState::Playing { return; }
+        let Some(&((x, y), previous)) = self.history.last() else { return; };
+        // Putting a flag back counts against the cap like placing it would
+        let out_of_flags = self.strict_flags && self.flags_placed() >= self.mine_total;
+        if previous == TileVisibility::Marked && self.board[(x, y)].tile_visibility != TileVisibility::Marked && out_of_flags { return; }
+        self.history.pop();
+        self.record(x, y, MoveKind::Undo);
+
+        // Tiles revealed since the toggle stay revealed
+        let tile = &mut self.board[(x, y)];
+        if tile.tile_visibility != TileVisibility::Visible {
+            tile.tile_visibility = previous;
+        }
+    }
+
+    // Returns the tiles it opened, nearest first
+    pub fn flood_reveal(&mut self, x: usize, y: usize) -> Vec<(usize, usize)> {
+        let mut revealed = Vec::new();
+        let tile = &mut self.board[(x, y)];
+        if !tile.is_clickable() { return revealed; }
         tile.tile_visibility = TileVisibility::Visible;
+        self.tiles_revealed += 1;
 
-        if tile.mine_count != 0 { return; }
+        // Breadth first, so every layer is one step further out than the one before
+        let mut layer = vec![(x, y)];
+        for depth in 0.. {
+            if layer.is_empty() { break; }
+            if self.reveal_layers.len() <= depth { self.reveal_layers.push(Vec::new()); }
+            self.reveal_layers[depth].extend(&layer);
+            revealed.extend(&layer);
 
-        for (x, y) in tile.neighbors(self.width, self.height) {
-            let neighbor_tile = &self.tiles[x + y * self.width];
-            if matches!(neighbor_tile.tile_visibility, TileVisibility::Hidden)
-            && matches!(neighbor_tile.tile_type, TileType::Safe) {
-                self.flood_reveal(x, y);
+            let mut next = Vec::new();
+            for (x, y) in layer {
+                let i = self.board.idx(x, y);
+                if self.board.tiles[i].mine_count != 0 { continue; }
+
+                for &j in &self.adjacency[i] {
+                    let neighbor_tile = &mut self.board.tiles[j];
+                    if neighbor_tile.is_clickable() && matches!(neighbor_tile.tile_type, TileType::Safe) {
+                        neighbor_tile.tile_visibility = TileVisibility::Visible;
+                        self.tiles_revealed += 1;
+                        next.push((neighbor_tile.x, neighbor_tile.y));
+                    }
+                }
             }
+            layer = next;
         }
+        revealed
     }
 
-    pub fn click_tile(&mut self) {
+    // Returns the safe tiles the click opened, chords included
+    pub fn click_tile(&mut self) -> Vec<(usize, usize)> {
         let (x, y) = self.selected;
-        let tile = &mut self.tiles[x + y * self.width];
+        if self.safe_reveal && self.proven_mine(x, y) { return Vec::new(); }
+        self.reveal(x, y);
+        self.reveal_layers.iter().flatten().copied().collect()
+    }
 
-        if matches!(tile.tile_visibility, TileVisibility::Marked) { return; }
+    pub fn reveal(&mut self, x: usize, y: usize) -> GameState {
+        if self.state != GameState::Playing { return self.state; }
+        self.record(x, y, MoveKind::Reveal);
+        self.reveal_layers.clear();
+        let Some(tile) = self.board.get(x, y) else { return self.state; };
+        // Layouts are played exactly as drawn, mines and all
+        let fixed = matches!(self.placement, MinePlacement::Fixed(_));
+        if !fixed && tile.is_clickable() && !self.board.tiles.iter().any(|x| x.tile_visibility == TileVisibility::Visible) {
+            self.clear_opening(x, y);
+        }
+        let tile = &mut self.board[(x, y)];
 
-        tile.tile_visibility = TileVisibility::Visible;
+        if matches!(tile.tile_visibility, TileVisibility::Visible) { return self.chord_at(x, y); }
+        if !tile.is_clickable() { return self.state; }
+
+        self.timer.start();
         match tile.tile_type {
-            TileType::Mine => self.end_game("You exploded >_<"),
-            TileType::Safe => self.flood_reveal(x, y),
+            TileType::Mine => {
+                tile.tile_visibility = TileVisibility::Visible;
+                self.detonated = Some((x, y));
+                self.finish(GameState::Lost);
+            },
+            TileType::Safe => {
+                self.flood_reveal(x, y);
+                if self.auto_chord { self.chord_at(x, y); }
+            },
         };
         self.check_game_won();
+        self.state
+    }
+
+    // The first reveal and all its neighbors are kept mine-free, any mines there move elsewhere.
+    // Relocation is seeded by the board so replays end up with the same layout
+    fn clear_opening(&mut self, x: usize, y: usize) {
+        let mut block = self.neighbors(&self.board[(x, y)]);
+        block.push((x, y));
+        let (clicked_x, clicked_y) = (x, y);
+
+        let (width, height) = (self.board.width, self.board.height);
+        let mut free: Vec<usize> = self.board.tiles.iter()
+            .filter(|x| matches!(x.tile_type, TileType::Safe) && !block.contains(&(x.x, x.y)))
+            .filter(|x| !self.safe_border || !Game::on_border(x.x, x.y, width, height))
+            .map(|x| self.board.idx(x.x, x.y))
+            .collect();
+        let mut rng = StdRng::seed_from_u64(self.seed);
+        for (x, y) in block {
+            if !matches!(self.board[(x, y)].tile_type, TileType::Mine) { continue; }
+            if free.is_empty() { break; }
+
+            let i = free.swap_remove(rng.gen_range(0..free.len()));
+            self.board.tiles[i].tile_type = TileType::Mine;
+            self.board[(x, y)].tile_type = TileType::Safe;
+        }
+
+        // Boards too crowded to clear the whole block still never lose on the first click,
+        // the mine swaps with a safe neighbor or, with none left anywhere, is taken off the board
+        if matches!(self.board[(clicked_x, clicked_y)].tile_type, TileType::Mine) {
+            let neighbor = self.neighbors(&self.board[(clicked_x, clicked_y)]).into_iter()
+                .filter(|&(x, y)| !self.safe_border || !Game::on_border(x, y, width, height))
+                .find(|&(x, y)| matches!(self.board[(x, y)].tile_type, TileType::Safe));
+            match neighbor {
+                Some(neighbor) => self.board[neighbor].tile_type = TileType::Mine,
+                None => self.mine_total -= 1,
+            }
+            self.board[(clicked_x, clicked_y)].tile_type = TileType::Safe;
+        }
+        self.recompute_counts();
+    }
+
+    // Chords the selected number and nothing else, covered tiles are left alone. It goes through
+    // reveal so it's recorded, a recorded reveal on a number replays as the same chord
+    pub fn chord(&mut self) -> GameState {
+        let (x, y) = self.selected;
+        if self.board[(x, y)].tile_visibility != TileVisibility::Visible { return self.state; }
+        self.reveal(x, y)
+    }
+
+    pub fn chord_at(&mut self, x: usize, y: usize) -> GameState {
+        if self.state != GameState::Playing { return self.state; }
+        let Some(tile) = self.board.get(x, y) else { return self.state; };
+
+        if !matches!(tile.tile_visibility, TileVisibility::Visible) || tile.mine_count == 0 { return self.state; }
+
+        let neighbors = self.neighbors(tile);
+        let marked = neighbors.iter()
+            .filter(|&&(x, y)| matches!(self.board[(x, y)].tile_visibility, TileVisibility::Marked))
+            .count();
+        if marked != tile.mine_count as usize { return self.state; }
+
+        for (x, y) in neighbors {
+            let neighbor_tile = &mut self.board[(x, y)];
+            if !neighbor_tile.is_clickable() { continue; }
+
+            match neighbor_tile.tile_type {
+                TileType::Mine => {
+                    neighbor_tile.tile_visibility = TileVisibility::Visible;
+                    self.detonated.get_or_insert((x, y));
+                    self.finish(GameState::Lost);
+                },
+                TileType::Safe => { self.flood_reveal(x, y); },
+            };
+        }
+        self.check_game_won();
+        self.state
+    }
+
+    pub fn auto_flag(&mut self) {
+        if self.state != GameState::Playing { return; }
+        let (x, y) = self.selected;
+        self.record(x, y, MoveKind::AutoFlag);
+
+        for i in 0..self.board.tiles.len() {
+            let tile = &self.board.tiles[i];
+            if !matches!(tile.tile_visibility, TileVisibility::Visible) || tile.mine_count == 0 { continue; }
+
+            let neighbors = self.neighbors(tile);
+            let hidden: Vec<usize> = neighbors.iter()
+                .map(|&(x, y)| self.board.idx(x, y))
+                .filter(|&i| self.board.tiles[i].is_clickable())
+                .collect();
+            let marked = neighbors.iter()
+                .filter(|&&(x, y)| matches!(self.board[(x, y)].tile_visibility, TileVisibility::Marked))
+                .count();
+
+            // Every unflagged hidden neighbor has to be one of the remaining mines
+            if hidden.len() + marked == tile.mine_count as usize {
+                for i in hidden {
+                    if self.strict_flags && self.flags_placed() >= self.mine_total { return; }
+                    self.board.tiles[i].tile_visibility = TileVisibility::Marked;
+                    self.flags_used += 1;
+                    self.flagged = true;
+                }
+            }
+        }
     }
 
+    // The auto-flag deduction for a single tile, some neighboring number has no other hidden tiles left to be its mines
+    fn proven_mine(&self, x: usize, y: usize) -> bool {
+        let Some(tile) = self.board.get(x, y) else { return false; };
+        if tile.tile_visibility == TileVisibility::Visible { return false; }
+
+        self.neighbors(tile).into_iter().any(|(x, y)| {
+            let number = &self.board[(x, y)];
+            if !matches!(number.tile_visibility, TileVisibility::Visible) || number.mine_count == 0 { return false; }
+            let covered = self.neighbors(number).iter()
+                .filter(|&&(x, y)| !matches!(self.board[(x, y)].tile_visibility, TileVisibility::Visible))
+                .count();
+            covered == number.mine_count as usize
+        })
+    }
+
+    pub fn hint(&self) -> Option<(usize, usize)> {
+        if self.state != GameState::Playing { return None; }
+
+        for tile in self.board.tiles.iter() {
+            if !matches!(tile.tile_visibility, TileVisibility::Visible) || tile.mine_count == 0 { continue; }
+
+            let neighbors = self.neighbors(tile);
+            let marked = neighbors.iter()
+                .filter(|&&(x, y)| matches!(self.board[(x, y)].tile_visibility, TileVisibility::Marked))
+                .count();
+
+            // A number satisfied by its flags leaves every other neighbor safe
+            if marked != tile.mine_count as usize { continue; }
+            if let Some(&safe) = neighbors.iter().find(|&&(x, y)| self.board[(x, y)].is_clickable()) {
+                return Some(safe);
+            }
+        }
+        None
+    }
+
+    // Compares pairs of numbers: when one's hidden neighbors all sit around another, the
+    // other's extra tiles hold exactly the difference in their remaining mines
+    fn subset_step(&mut self) -> bool {
+        let remaining = |game: &Game, i: usize| {
+            let tile = &game.board.tiles[i];
+            let neighbors = game.neighbors(tile);
+            let hidden: Vec<usize> = neighbors.iter()
+                .map(|&(x, y)| game.board.idx(x, y))
+                .filter(|&i| game.board.tiles[i].is_clickable())
+                .collect();
+            let marked = neighbors.iter()
+                .filter(|&&(x, y)| matches!(game.board[(x, y)].tile_visibility, TileVisibility::Marked))
+                .count();
+            (hidden, (tile.mine_count as usize).saturating_sub(marked))
+        };
+
+        for a in 0..self.board.tiles.len() {
+            let tile = &self.board.tiles[a];
+            if !matches!(tile.tile_visibility, TileVisibility::Visible) || tile.mine_count == 0 { continue; }
+            let (hidden_a, left_a) = remaining(self, a);
+            if hidden_a.is_empty() { continue; }
+
+            // Only numbers next to one of a's hidden tiles can surround all of them
+            for (bx, by) in self.neighbors(&self.board.tiles[hidden_a[0]]) {
+                let b = self.board.idx(bx, by);
+                let number = &self.board.tiles[b];
+                if b == a || !matches!(number.tile_visibility, TileVisibility::Visible) || number.mine_count == 0 { continue; }
+                let (hidden_b, left_b) = remaining(self, b);
+                if hidden_b.len() == hidden_a.len() || !hidden_a.iter().all(|i| hidden_b.contains(i)) { continue; }
+
+                let extra: Vec<(usize, usize)> = hidden_b.iter()
+                    .filter(|i| !hidden_a.contains(i))
+                    .map(|&i| (self.board.tiles[i].x, self.board.tiles[i].y))
+                    .collect();
+                if left_b == left_a {
+                    for (x, y) in extra { self.reveal(x, y); }
+                    return true;
+                }
+                if left_b == left_a + extra.len() {
+                    let flags = self.flags_placed();
+                    for (x, y) in extra {
+                        if self.board[(x, y)].tile_visibility == TileVisibility::Hidden { self.mark(x, y); }
+                    }
+                    if self.flags_placed() != flags { return true; }
+                }
+            }
+        }
+        false
+    }
+
+    // Applies the auto-flag and hint deductions until neither finds anything new,
+    // returns whether that alone was enough to clear the board
+    pub fn solve(&mut self) -> bool {
+        while self.state == GameState::Playing {
+            let flags = self.flags_placed();
+            self.auto_flag();
+            let mut progress = self.flags_placed() != flags;
+
+            while let Some((x, y)) = self.hint() {
+                self.reveal(x, y);
+                progress = true;
+            }
+            if !progress && !self.subset_step() { break; }
+        }
+        self.state == GameState::Won
+    }
+
+    // Rough per-tile mine odds, averaging what each adjacent number says about its hidden neighbors
+    pub fn mine_probabilities(&self) -> Vec<Option<f64>> {
+        let mut sums = vec![(0.0, 0); self.board.tiles.len()];
+
+        for tile in self.board.tiles.iter() {
+            if !matches!(tile.tile_visibility, TileVisibility::Visible) || tile.mine_count == 0 { continue; }
+
+            let neighbors = self.neighbors(tile);
+            let hidden: Vec<usize> = neighbors.iter()
+                .map(|&(x, y)| self.board.idx(x, y))
+                .filter(|&i| self.board.tiles[i].is_clickable())
+                .collect();
+            let marked = neighbors.iter()
+                .filter(|&&(x, y)| matches!(self.board[(x, y)].tile_visibility, TileVisibility::Marked))
+                .count();
+            if hidden.is_empty() { continue; }
+
+            let probability = (tile.mine_count as f64 - marked as f64).clamp(0.0, hidden.len() as f64) / hidden.len() as f64;
+            for i in hidden {
+                sums[i].0 += probability;
+                sums[i].1 += 1;
+            }
+        }
+
+        sums.into_iter()
+            .map(|(sum, count)| if count == 0 { None } else { Some(sum / count as f64) })
+            .collect()
+    }
+
+    // Describes the first thing found that should never happen, for fuzzing
+    pub fn broken_invariant(&self) -> Option<String> {
+        let mines = self.board.tiles.iter().filter(|x| matches!(x.tile_type, TileType::Mine)).count();
+        if mines != self.mine_total {
+            return Some(format!("{} mines on the board but mine_total is {}", mines, self.mine_total));
+        }
+        for (i, tile) in self.board.tiles.iter().enumerate() {
+            let count = self.adjacency[i].iter()
+                .filter(|&&j| matches!(self.board.tiles[j].tile_type, TileType::Mine))
+                .count();
+            if count != tile.mine_count as usize {
+                return Some(format!("tile {},{} shows {} but has {} adjacent mines", tile.x, tile.y, tile.mine_count, count));
+            }
+            if matches!(tile.tile_type, TileType::Mine) && tile.tile_visibility == TileVisibility::Visible && self.state == GameState::Playing {
+                return Some(format!("mine at {},{} is revealed while still playing", tile.x, tile.y));
+            }
+        }
+
+        let revealed = self.board.tiles.iter()
+            .filter(|x| matches!(x.tile_type, TileType::Safe) && x.tile_visibility == TileVisibility::Visible)
+            .count();
+        if self.state != GameState::Surrendered && revealed != self.tiles_revealed {
+            return Some(format!("{} safe tiles revealed but tiles_revealed is {}", revealed, self.tiles_revealed));
+        }
+        if self.state == GameState::Won && self.safe_remaining() > 0 {
+            return Some(format!("won with {} safe tiles left", self.safe_remaining()));
+        }
+        if self.state == GameState::Playing && self.strict_flags && self.flags_placed() > self.mine_total {
+            return Some(format!("{} flags placed past the cap of {}", self.flags_placed(), self.mine_total));
+        }
+        None
+    }
+
+    // The board as plain text, one line per row with the same glyphs the screen uses, for snapshot tests
+    #[cfg(test)]
+    pub fn render_string(&self) -> String {
+        let glyphs = crate::theme::Glyphs::default();
+        let rows = self.board.tiles.chunks(self.board.width.max(1)).map(|row| row.iter().map(|tile| {
+            match (tile.tile_visibility, &tile.tile_type) {
+                (TileVisibility::Hidden, _) => glyphs.hidden,
+                (TileVisibility::Questioned, _) => glyphs.questioned,
+                (TileVisibility::Marked, TileType::Safe) if self.state == GameState::Lost => glyphs.wrong_mark,
+                (TileVisibility::Marked, _) => glyphs.marked,
+                (TileVisibility::Visible, TileType::Mine) => glyphs.mine,
+                (TileVisibility::Visible, TileType::Safe) if tile.mine_count == 0 => glyphs.empty,
+                (TileVisibility::Visible, TileType::Safe) => char::from(b'0' + tile.mine_count),
+            }
+        }).collect::<String>());
+        rows.collect::<Vec<String>>().join("\n")
+    }
+
+    // Positions outside the board are ignored, callers are expected to only send valid ones
     pub fn set_selected(&mut self, pos: (i32, i32)) {
         let (x, y) = pos;
-        if x >= 0 && x < self.width as i32
-        && y >= 0 && y < self.height as i32 {
+        if x >= 0 && x < self.board.width as i32
+        && y >= 0 && y < self.board.height as i32 {
             self.selected = (x as usize, y as usize);
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Boards drawn with '*' for mines and '.' for safe tiles, mines stay where they're drawn
+    fn board(layout: &str) -> Game {
+        Game::from_layout(layout).unwrap()
+    }
+
+    fn neighbors_of(game: &Game, x: usize, y: usize) -> Vec<(usize, usize)> {
+        let mut neighbors = game.neighbors(&game.board[(x, y)]);
+        neighbors.sort();
+        neighbors
+    }
+
+    #[test]
+    fn neighbors_of_corners_edges_and_the_middle() {
+        let game = Game::new_seeded(4, 4, 1).unwrap();
+        assert_eq!(neighbors_of(&game, 0, 0), vec![(0, 1), (1, 0), (1, 1)]);
+        assert_eq!(neighbors_of(&game, 3, 3), vec![(2, 2), (2, 3), (3, 2)]);
+        assert_eq!(neighbors_of(&game, 2, 0), vec![(1, 0), (1, 1), (2, 1), (3, 0), (3, 1)]);
+        assert_eq!(neighbors_of(&game, 0, 2), vec![(0, 1), (0, 3), (1, 1), (1, 2), (1, 3)]);
+        assert_eq!(neighbors_of(&game, 1, 2), vec![(0, 1), (0, 2), (0, 3), (1, 1), (1, 3), (2, 1), (2, 2), (2, 3)]);
+    }
+
+    // Two openings that only touch corner to corner, at (1, 1) and (2, 2)
+    const DIAGONAL_OPENINGS: &str = "...*\n....\n....\n*...";
+
+    #[test]
+    fn floods_cross_diagonal_gaps_between_openings() {
+        for (x, y) in [(0, 0), (3, 3)] {
+            let mut game = board(DIAGONAL_OPENINGS);
+            assert_eq!((game.board[(2, 1)].mine_count, game.board[(1, 2)].mine_count), (1, 1));
+            game.reveal(x, y);
+            assert!(game.board.tiles.iter()
+                .filter(|x| matches!(x.tile_type, TileType::Safe))
+                .all(|x| x.tile_visibility == TileVisibility::Visible));
+            assert_eq!(game.state, GameState::Won);
+        }
+    }
+
+    fn visibility(game: &Game, x: usize, y: usize) -> TileVisibility {
+        game.board[(x, y)].tile_visibility
+    }
+
+    fn mines(game: &Game) -> Vec<(usize, usize)> {
+        game.board.tiles.iter().filter(|x| matches!(x.tile_type, TileType::Mine)).map(|x| (x.x, x.y)).collect()
+    }
+
+    fn revealed(game: &Game) -> Vec<(usize, usize)> {
+        game.board.tiles.iter().filter(|x| x.tile_visibility == TileVisibility::Visible).map(|x| (x.x, x.y)).collect()
+    }
+
+    #[test]
+    fn scripted_games_play_out_to_a_win_or_a_loss() {
+        let seeded = || Game::with_mines_seeded(6, 5, 6, 7).unwrap();
+        let mut game = seeded();
+        assert_eq!(game.reveal(0, 0), GameState::Playing);
+        assert_eq!(revealed(&game), vec![(0, 0), (1, 0), (0, 1), (1, 1)]);
+        assert_eq!(mines(&game), vec![(2, 0), (4, 0), (2, 1), (4, 1), (1, 2), (1, 3)]);
+
+        // A flag on a mine keeps it shut, the next reveal floods the far corner
+        game.mark(1, 2);
+        assert_eq!(game.reveal(1, 2), GameState::Playing);
+        assert_eq!(game.reveal(5, 4), GameState::Playing);
+        assert_eq!(revealed(&game).len(), 4 + 12);
+        assert_eq!(visibility(&game, 1, 2), TileVisibility::Marked);
+
+        let hidden: Vec<(usize, usize)> = game.board.tiles.iter()
+            .filter(|x| matches!(x.tile_type, TileType::Safe) && x.tile_visibility != TileVisibility::Visible)
+            .map(|x| (x.x, x.y))
+            .collect();
+        let (&last, rest) = hidden.split_last().unwrap();
+        for &(x, y) in rest {
+            assert_eq!(game.reveal(x, y), GameState::Playing);
+        }
+        assert_eq!(game.reveal(last.0, last.1), GameState::Won);
+        assert_eq!(revealed(&game).len(), 6 * 5 - 6);
+        assert!(mines(&game).iter().all(|&(x, y)| visibility(&game, x, y) == TileVisibility::Marked));
+
+        let mut game = seeded();
+        game.reveal(0, 0);
+        assert_eq!(game.reveal(2, 1), GameState::Lost);
+        assert_eq!(game.reveal(5, 4), GameState::Lost);
+        assert_eq!(revealed(&game).len(), 4 + 6);
+    }
+
+    #[test]
+    fn the_last_safe_reveal_wins_whatever_the_mines_show() {
+        // Both safe tiles are numbers so neither floods into the other. Every ordering of the two
+        // reveals and two flags is tried, leaving the mines flagged, hidden or one of each at the end
+        let steps = [(1, 0, false), (2, 0, false), (0, 0, true), (3, 0, true)];
+        let orders = (0..4usize.pow(4))
+            .map(|x| [x % 4, x / 4 % 4, x / 16 % 4, x / 64])
+            .filter(|x| (0..4).all(|i| x.contains(&i)));
+        for order in orders {
+            for flags in [0, 1, 2] {
+                let mut game = board("*..*");
+                let mut safe_left = 2;
+                for &i in order.iter().filter(|&&i| i < 2 + flags) {
+                    let (x, y, flag) = steps[i];
+                    let state = if flag { game.mark(x, y) } else { safe_left -= 1; game.reveal(x, y) };
+                    let expected = if safe_left == 0 { GameState::Won } else { GameState::Playing };
+                    assert_eq!(state, expected, "{:?} with {} flags", order, flags);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn a_flagged_safe_tile_holds_back_the_win() {
+        let mut game = board("*..*");
+        game.mark(2, 0);
+        assert_eq!(game.reveal(1, 0), GameState::Playing);
+        assert_eq!(game.mark(0, 0), GameState::Playing);
+        assert_eq!(game.mark(3, 0), GameState::Playing);
+        // The flag turns into a question mark, which can be revealed
+        assert_eq!(game.mark(2, 0), GameState::Playing);
+        assert_eq!(game.reveal(2, 0), GameState::Won);
+    }
+
+    #[test]
+    fn three_bv_counts_openings_and_lone_numbers() {
+        // One click per opening, numbers on an opening's edge come with it, every other number takes its own
+        for (layout, three_bv) in [
+            ("...\n.*.\n...", 8),
+            ("*..", 1),
+            ("..*..", 2),
+            (".*.*.", 3),
+            ("*...\n....\n....\n....", 1),
+            (DIAGONAL_OPENINGS, 1),
+        ] {
+            assert_eq!(board(layout).three_bv(), three_bv, "{}", layout);
+        }
+    }
+
+    #[test]
+    fn empty_boards_are_rejected_by_every_constructor() {
+        assert!(matches!(Game::try_new(0, 5), Err(GameError::EmptyBoard { .. })));
+        assert!(matches!(Game::new_seeded(5, 0, 1), Err(GameError::EmptyBoard { .. })));
+        assert!(matches!(Game::with_density(0, 0, 0.5), Err(GameError::EmptyBoard { .. })));
+        assert!(matches!(Game::with_mines_seeded(0, 5, 1, 1), Err(GameError::EmptyBoard { .. })));
+        assert!(matches!(Game::new_seeded(usize::MAX, 2, 1), Err(GameError::TooLarge { .. })));
+    }
+
+    #[test]
+    fn adjacency_is_symmetric_on_every_topology() {
+        for (width, height) in (1..=7).flat_map(|w| (1..=7).map(move |h| (w, h))) {
+            for (wrap, topology) in [(false, Topology::Square), (true, Topology::Square), (false, Topology::Hex), (true, Topology::Hex)] {
+                let mut game = board(&vec![".".repeat(width); height].join("\n"));
+                game.set_layout(wrap, topology);
+                for (i, neighbors) in game.adjacency.iter().enumerate() {
+                    for &j in neighbors {
+                        assert!(game.adjacency[j].contains(&i), "{}x{} {:?} wrap {}: {} -> {} is one way", width, height, topology, wrap, i, j);
+                    }
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn wrapped_boards_count_and_flood_across_the_seam() {
+        let mut game = board("....\n....\n...*");
+        game.set_layout(true, Topology::Square);
+        assert!(neighbors_of(&game, 0, 0).contains(&(3, 2)));
+        assert_eq!(game.board[(0, 0)].mine_count, 1);
+
+        // The left column's zeros open the right one through the edge, the mines sit between them
+        let mut game = board("..*..\n..*..\n..*..");
+        game.set_layout(true, Topology::Square);
+        game.reveal(0, 1);
+        assert_eq!(visibility(&game, 4, 1), TileVisibility::Visible);
+        assert_eq!(game.state, GameState::Won);
+    }
+
+    #[test]
+    fn flag_or_reveal_keeps_a_flag_safe_reveal_refuses_to_open() {
+        let mut game = board("*..*.");
+        game.safe_reveal = true;
+        game.reveal(4, 0);
+        game.selected = (3, 0);
+        game.flag_or_reveal();
+        assert_eq!(visibility(&game, 3, 0), TileVisibility::Marked);
+        game.flag_or_reveal();
+        assert_eq!(visibility(&game, 3, 0), TileVisibility::Marked);
+        assert_eq!(game.state, GameState::Playing);
+    }
+
+    #[test]
+    fn injected_generators_fix_the_mines() {
+        let game = Game::with_mines_rng(6, 4, 5, &mut rand::rngs::mock::StepRng::new(42, 1)).unwrap();
+        assert_eq!(game.seed, 42);
+        assert_eq!(mines(&game), vec![(5, 0), (3, 1), (4, 1), (2, 2), (1, 3)]);
+
+        let mut reset = Game::with_mines_seeded(6, 4, 5, 1).unwrap();
+        reset.reset_rng(&mut rand::rngs::mock::StepRng::new(42, 1));
+        assert_eq!(mines(&reset), mines(&game));
+    }
+
+    #[test]
+    fn chords_are_recorded_and_replay() {
+        let mut game = board("*....\n.....\n.....\n....*");
+        game.reveal(1, 1);
+        game.mark(0, 0);
+        // Covered tiles aren't chorded, or revealed
+        game.selected = (4, 3);
+        game.chord();
+        assert_eq!(visibility(&game, 4, 3), TileVisibility::Hidden);
+
+        game.selected = (1, 1);
+        let moves = game.moves.len();
+        game.chord();
+        assert_eq!(game.moves.len(), moves + 1);
+
+        let mut replayed = game.replay_board();
+        game.moves.iter().for_each(|&x| replayed.apply(x));
+        assert_eq!(replayed.render_string(), game.render_string());
+    }
+
+    #[test]
+    fn auto_flag_counts_its_flags() {
+        let mut game = board("*..*.");
+        game.reveal(4, 0);
+        game.auto_flag();
+        assert_eq!(visibility(&game, 3, 0), TileVisibility::Marked);
+        assert_eq!(game.flags_used, game.flags_placed());
+    }
+
+    #[test]
+    fn flood_reveal_opens_a_large_board_without_recursing() {
+        let mut game = board(&vec![".".repeat(200); 200].join("\n"));
+        game.flood_reveal(0, 0);
+        assert!(game.board.tiles.iter().all(|x| x.tile_visibility == TileVisibility::Visible));
+        assert_eq!(game.tiles_revealed, 200 * 200);
+    }
+
+    #[test]
+    fn chording_opens_the_rest_or_sets_off_a_misplaced_flag() {
+        let mut game = board("*.*\n...\n...");
+        game.reveal(1, 2);
+        assert_eq!(visibility(&game, 1, 0), TileVisibility::Hidden);
+        game.mark(0, 0);
+        game.chord_at(0, 1);
+        assert_eq!(visibility(&game, 1, 0), TileVisibility::Visible);
+        assert_eq!(game.state, GameState::Won);
+
+        let mut game = board("*.*\n...\n...");
+        game.reveal(1, 2);
+        game.mark(1, 0);
+        game.chord_at(0, 1);
+        assert_eq!(game.state, GameState::Lost);
+        assert_eq!(game.detonated, Some((0, 0)));
+        assert_eq!(visibility(&game, 1, 0), TileVisibility::Marked);
+    }
+
+    #[test]
+    fn losing_shows_every_mine_and_keeps_wrong_flags() {
+        let mut game = board("*.*\n...\n..*");
+        game.mark(1, 0);
+        game.reveal(2, 0);
+        assert_eq!(game.state, GameState::Lost);
+        assert_eq!(game.detonated, Some((2, 0)));
+        assert!(mines(&game).iter().all(|&(x, y)| visibility(&game, x, y) == TileVisibility::Visible));
+        assert_eq!(visibility(&game, 1, 0), TileVisibility::Marked);
+        assert_eq!(visibility(&game, 1, 1), TileVisibility::Hidden);
+    }
+
+    #[test]
+    fn saved_games_load_back_and_play_on() {
+        let mut game = board("*....\n.....\n.....\n....*");
+        game.set_layout(true, Topology::Hex);
+        game.reveal(2, 2);
+        game.mark(0, 0);
+        game.selected = (3, 1);
+
+        let path = std::env::temp_dir().join(format!("rust-mines-test-{}.json", std::process::id()));
+        game.save(&path).unwrap();
+        let mut loaded = Game::load(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+
+        assert_eq!(loaded.render_string(), game.render_string());
+        assert_eq!(loaded.selected, game.selected);
+        assert_eq!(loaded.moves.len(), game.moves.len());
+        assert_eq!(loaded.adjacency, game.adjacency);
+        assert_eq!((loaded.state, loaded.flags_used, loaded.tiles_revealed), (game.state, game.flags_used, game.tiles_revealed));
+
+        game.solve();
+        loaded.solve();
+        assert_eq!(loaded.render_string(), game.render_string());
+        assert_eq!(loaded.state, game.state);
+    }
+
+    #[test]
+    fn one_auto_flag_pass_flags_only_what_the_numbers_prove() {
+        let mut game = board("*.*..\n.....\n.*...\n.....");
+        game.reveal(4, 3);
+        game.auto_flag();
+        let flagged = |game: &Game| game.board.tiles.iter()
+            .filter(|x| x.tile_visibility == TileVisibility::Marked)
+            .map(|x| (x.x, x.y))
+            .collect::<Vec<_>>();
+        // (1, 2) is a mine too, but no number pins it down yet
+        assert_eq!(flagged(&game), vec![(2, 0)]);
+        game.auto_flag();
+        assert_eq!(flagged(&game), vec![(2, 0)]);
+    }
+
+    #[test]
+    fn flags_hold_back_floods_and_reveals() {
+        let mut game = board("....\n....\n....\n...*");
+        game.mark(3, 3);
+        game.mark(1, 1);
+        game.mark(2, 2);
+        game.mark(0, 3);
+        game.mark(0, 3);
+        game.reveal(1, 1);
+        assert_eq!(visibility(&game, 1, 1), TileVisibility::Marked);
+
+        game.reveal(0, 0);
+        assert_eq!(visibility(&game, 3, 3), TileVisibility::Marked);
+        assert_eq!(visibility(&game, 1, 1), TileVisibility::Marked);
+        assert_eq!(visibility(&game, 2, 2), TileVisibility::Marked);
+        // Question marks don't stop anything
+        assert_eq!(visibility(&game, 0, 3), TileVisibility::Visible);
+        assert_eq!(visibility(&game, 3, 0), TileVisibility::Visible);
+        assert_eq!(game.state, GameState::Playing);
+    }
+
+    #[test]
+    fn solver_clears_what_deduction_can_and_stops_at_a_guess() {
+        let mut game = board(".*..\n.*..\n....");
+        game.reveal(3, 2);
+        assert!(game.solve());
+        assert_eq!(game.state, GameState::Won);
+
+        // The 2 could mean any two of the three tiles around it
+        let mut game = board("*.\n.*");
+        game.reveal(1, 0);
+        let before = game.render_string();
+        assert!(!game.solve());
+        assert_eq!(game.state, GameState::Playing);
+        assert_eq!(game.render_string(), before);
+
+        // No single number settles the top row, but the 1 in the corner's two tiles both
+        // sit around the 2 beside it, so the 2's third tile has to be its other mine
+        let mut game = board("*.*.\n....\n....");
+        game.reveal(0, 2);
+        assert!(game.solve());
+        assert_eq!(visibility(&game, 2, 0), TileVisibility::Marked);
+    }
+
+    #[test]
+    fn first_reveals_open_onto_a_mine_free_block() {
+        for seed in 0..200 {
+            for (x, y) in [(4, 4), (0, 0), (8, 3)] {
+                let mut game = Game::with_mines_seeded(9, 9, 30, seed).unwrap();
+                game.reveal(x, y);
+                let block = game.neighbors(&game.board[(x, y)]).into_iter().chain([(x, y)]);
+                for (x, y) in block {
+                    assert!(matches!(game.board[(x, y)].tile_type, TileType::Safe), "seed {} left a mine at {},{}", seed, x, y);
+                }
+                assert_eq!(game.mine_total, 30);
+                assert_eq!(game.broken_invariant(), None);
+            }
+        }
+    }
+
+    #[test]
+    fn single_row_and_column_boards_have_in_line_neighbors() {
+        let row = board(".....");
+        assert_eq!(neighbors_of(&row, 0, 0), vec![(1, 0)]);
+        assert_eq!(neighbors_of(&row, 2, 0), vec![(1, 0), (3, 0)]);
+        assert_eq!(neighbors_of(&row, 4, 0), vec![(3, 0)]);
+
+        let column = board(".\n.\n.\n.\n.");
+        assert_eq!(neighbors_of(&column, 0, 0), vec![(0, 1)]);
+        assert_eq!(neighbors_of(&column, 0, 2), vec![(0, 1), (0, 3)]);
+        assert_eq!(neighbors_of(&column, 0, 4), vec![(0, 3)]);
+
+        let mut single = board(".");
+        assert!(neighbors_of(&single, 0, 0).is_empty());
+        assert_eq!(single.reveal(0, 0), GameState::Won);
+    }
+
+    fn counts(game: &Game) -> Vec<u8> {
+        game.board.tiles.iter().map(|x| x.mine_count).collect()
+    }
+
+    #[test]
+    fn counts_follow_the_mines_when_recomputed() {
+        let mut game = board("*..\n.*.\n...");
+        assert_eq!(counts(&game), vec![1, 2, 1, 2, 1, 1, 1, 1, 1]);
+
+        game.board[(1, 1)].tile_type = TileType::Safe;
+        game.board[(2, 2)].tile_type = TileType::Mine;
+        game.recompute_counts();
+        assert_eq!(counts(&game), vec![0, 1, 0, 1, 2, 1, 0, 1, 0]);
+    }
+
+    #[test]
+    fn render_string_matches_golden_boards() {
+        let mut game = board("*.*..\n.....\n.*...\n.....");
+        assert_eq!(game.render_string(), "□□□□□\n□□□□□\n□□□□□\n□□□□□");
+        game.reveal(4, 3);
+        game.mark(2, 0);
+        game.mark(0, 3);
+        game.mark(0, 3);
+        assert_eq!(game.render_string(), "□□■1·\n□□21·\n□□1··\n?□1··");
+
+        // Losing shows the flagged mine as a mine, and leaves the wrong flag at (1, 1) standing
+        game.mark(1, 1);
+        game.reveal(0, 0);
+        assert_eq!(game.render_string(), "☓□☓1·\n□■21·\n□☓1··\n?□1··");
+    }
+
+    #[test]
+    fn first_reveals_are_safe_with_one_safe_tile_left() {
+        for (width, height) in [(3, 3), (4, 4), (1, 2), (5, 1)] {
+            let mines = width * height - 1;
+            for seed in 0..20 {
+                for (x, y) in (0..width).flat_map(|x| (0..height).map(move |y| (x, y))) {
+                    let mut game = Game::with_mines_seeded(width, height, mines, seed).unwrap();
+                    assert_eq!(game.reveal(x, y), GameState::Won, "{}x{} seed {} at {},{}", width, height, seed, x, y);
+                    assert_eq!(game.mine_total, mines);
+                    assert_eq!(game.broken_invariant(), None);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn random_density_always_leaves_a_mine_and_a_safe_tile() {
+        for seed in 0..500 {
+            for (width, height) in [(2, 2), (1, 2), (9, 9)] {
+                for ratio in [0.0, 0.01, 0.99, 1.0, f64::NAN] {
+                    let game = Game::generate(width, height, ratio, seed).unwrap();
+                    let mines = mines(&game).len();
+                    assert!(mines >= 1 && mines < width * height, "{}x{} at {} seed {}: {} mines", width, height, ratio, seed, mines);
+                    assert_eq!(game.mine_total, mines);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn same_board_restarts_bring_back_a_solvable_opening() {
+        for seed in 0..10 {
+            let fresh = Game::new_solvable(16, 16, 40, seed).unwrap();
+            assert!(matches!(fresh.placement, MinePlacement::Solvable(_)));
+            let mut game = Game::new_solvable(16, 16, 40, seed).unwrap();
+            game.solve();
+            game.reset_same();
+            assert_eq!(game.render_string(), fresh.render_string());
+            assert_eq!(game.moves.len(), fresh.moves.len());
+        }
+    }
+}
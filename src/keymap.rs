@@ -0,0 +1,267 @@
+use ratatui::crossterm::event::KeyCode;
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Action {
+    Quit,
+    Left,
+    Right,
+    Up,
+    Down,
+    Click,
+    Mark,
+    Restart,
+    RestartSame,
+    Save,
+    AutoFlag,
+    Hint,
+    ToggleGlyphs,
+    Undo,
+    ToggleProbabilities,
+    Solve,
+    Pause,
+    ToggleStats,
+    ToggleRulers,
+    RevealAll,
+    RowStart,
+    RowEnd,
+    Top,
+    Bottom,
+    NewTab,
+    CloseTab,
+    NextTab,
+    PrevTab,
+    ToggleNotes,
+    FlagOrReveal,
+    Peek,
+    ToggleNeighbors,
+}
+
+#[derive(Clone)]
+pub struct Keymap {
+    pub quit: Vec<KeyCode>,
+    pub left: Vec<KeyCode>,
+    pub right: Vec<KeyCode>,
+    pub up: Vec<KeyCode>,
+    pub down: Vec<KeyCode>,
+    pub click: Vec<KeyCode>,
+    pub mark: Vec<KeyCode>,
+    pub restart: Vec<KeyCode>,
+    pub restart_same: Vec<KeyCode>,
+    pub save: Vec<KeyCode>,
+    pub auto_flag: Vec<KeyCode>,
+    pub hint: Vec<KeyCode>,
+    pub toggle_glyphs: Vec<KeyCode>,
+    pub undo: Vec<KeyCode>,
+    pub toggle_probabilities: Vec<KeyCode>,
+    pub solve: Vec<KeyCode>,
+    pub pause: Vec<KeyCode>,
+    pub toggle_stats: Vec<KeyCode>,
+    pub toggle_rulers: Vec<KeyCode>,
+    pub reveal_all: Vec<KeyCode>,
+    pub row_start: Vec<KeyCode>,
+    pub row_end: Vec<KeyCode>,
+    pub top: Vec<KeyCode>,
+    pub bottom: Vec<KeyCode>,
+    pub new_tab: Vec<KeyCode>,
+    pub close_tab: Vec<KeyCode>,
+    pub next_tab: Vec<KeyCode>,
+    pub prev_tab: Vec<KeyCode>,
+    pub toggle_notes: Vec<KeyCode>,
+    pub flag_or_reveal: Vec<KeyCode>,
+    pub peek: Vec<KeyCode>,
+    pub toggle_neighbors: Vec<KeyCode>,
+}
+
+impl Default for Keymap {
+    fn default() -> Keymap {
+        Keymap {
+            quit: vec![KeyCode::Esc, KeyCode::Char('q')],
+            left: vec![KeyCode::Left, KeyCode::Char('h')],
+            right: vec![KeyCode::Right, KeyCode::Char('l')],
+            up: vec![KeyCode::Up, KeyCode::Char('k')],
+            down: vec![KeyCode::Down, KeyCode::Char('j')],
+            click: vec![KeyCode::Char(' ')],
+            mark: vec![KeyCode::Char('f')],
+            restart: vec![KeyCode::Char('r')],
+            restart_same: vec![KeyCode::Char('R')],
+            save: vec![KeyCode::Char('s')],
+            auto_flag: vec![KeyCode::Char('a')],
+            hint: vec![KeyCode::Char('i')],
+            toggle_glyphs: vec![KeyCode::Char('c')],
+            undo: vec![KeyCode::Char('u')],
+            toggle_probabilities: vec![KeyCode::Char('o')],
+            solve: vec![KeyCode::Char('v')],
+            pause: vec![KeyCode::Char('p')],
+            toggle_stats: vec![KeyCode::Char('t')],
+            toggle_rulers: vec![KeyCode::Char('g')],
+            reveal_all: vec![KeyCode::Char('X')],
+            row_start: vec![KeyCode::Home, KeyCode::Char('0')],
+            row_end: vec![KeyCode::End, KeyCode::Char('$')],
+            top: vec![KeyCode::PageUp],
+            bottom: vec![KeyCode::PageDown],
+            new_tab: vec![KeyCode::Char('n')],
+            close_tab: vec![KeyCode::Char('w')],
+            next_tab: vec![KeyCode::Tab],
+            prev_tab: vec![KeyCode::BackTab],
+            toggle_notes: vec![KeyCode::Char('m')],
+            // Opt-in only, bind it in config.toml to use it
+            flag_or_reveal: Vec::new(),
+            peek: vec![KeyCode::Char('e')],
+            toggle_neighbors: vec![KeyCode::Char('b')],
+        }
+    }
+}
+
+impl Keymap {
+    fn bindings(&self) -> [(&Vec<KeyCode>, Action); 32] {
+        [
+            (&self.quit, Action::Quit),
+            (&self.left, Action::Left),
+            (&self.right, Action::Right),
+            (&self.up, Action::Up),
+            (&self.down, Action::Down),
+            (&self.click, Action::Click),
+            (&self.mark, Action::Mark),
+            (&self.restart, Action::Restart),
+            (&self.restart_same, Action::RestartSame),
+            (&self.save, Action::Save),
+            (&self.auto_flag, Action::AutoFlag),
+            (&self.hint, Action::Hint),
+            (&self.toggle_glyphs, Action::ToggleGlyphs),
+            (&self.undo, Action::Undo),
+            (&self.toggle_probabilities, Action::ToggleProbabilities),
+            (&self.solve, Action::Solve),
+            (&self.pause, Action::Pause),
+            (&self.toggle_stats, Action::ToggleStats),
+            (&self.toggle_rulers, Action::ToggleRulers),
+            (&self.reveal_all, Action::RevealAll),
+            (&self.row_start, Action::RowStart),
+            (&self.row_end, Action::RowEnd),
+            (&self.top, Action::Top),
+            (&self.bottom, Action::Bottom),
+            (&self.new_tab, Action::NewTab),
+            (&self.close_tab, Action::CloseTab),
+            (&self.next_tab, Action::NextTab),
+            (&self.prev_tab, Action::PrevTab),
+            (&self.toggle_notes, Action::ToggleNotes),
+            (&self.flag_or_reveal, Action::FlagOrReveal),
+            (&self.peek, Action::Peek),
+            (&self.toggle_neighbors, Action::ToggleNeighbors),
+        ]
+    }
+
+    pub fn action(&self, code: KeyCode) -> Option<Action> {
+        self.bindings().into_iter()
+            .find(|(codes, _)| codes.contains(&code))
+            .map(|(_, action)| action)
+    }
+
+    // Key hints like "[r] New board  [Esc] Quit" from the first key bound to each action,
+    // actions left unbound are dropped since there's nothing to press
+    pub fn hints(&self, hints: &[(Action, &str)]) -> String {
+        let bindings = self.bindings();
+        hints.iter()
+            .filter_map(|&(action, label)| {
+                let (codes, _) = bindings.iter().find(|(_, x)| *x == action)?;
+                codes.first().map(|&code| format!("[{}] {}", Keymap::key_name(code), label))
+            })
+            .collect::<Vec<String>>()
+            .join("  ")
+    }
+
+    // The reverse of parse_key, keys are shown the way config.toml spells them
+    fn key_name(code: KeyCode) -> String {
+        match code {
+            KeyCode::Char(' ') => "Space".to_string(),
+            KeyCode::Char(c) => c.to_string(),
+            code => format!("{:?}", code),
+        }
+    }
+
+    // Replaces the keys bound to an action, returns false for unknown actions or keys
+    pub fn bind(&mut self, action: &str, keys: &[String]) -> bool {
+        let Some(codes) = keys.iter().map(|x| Keymap::parse_key(x)).collect::<Option<Vec<KeyCode>>>() else {
+            return false;
+        };
+        let binding = match action {
+            "quit" => &mut self.quit,
+            "left" => &mut self.left,
+            "right" => &mut self.right,
+            "up" => &mut self.up,
+            "down" => &mut self.down,
+            "click" => &mut self.click,
+            "mark" => &mut self.mark,
+            "restart" => &mut self.restart,
+            "restart_same" => &mut self.restart_same,
+            "save" => &mut self.save,
+            "auto_flag" => &mut self.auto_flag,
+            "hint" => &mut self.hint,
+            "toggle_glyphs" => &mut self.toggle_glyphs,
+            "undo" => &mut self.undo,
+            "toggle_probabilities" => &mut self.toggle_probabilities,
+            "solve" => &mut self.solve,
+            "pause" => &mut self.pause,
+            "toggle_stats" => &mut self.toggle_stats,
+            "toggle_rulers" => &mut self.toggle_rulers,
+            "reveal_all" => &mut self.reveal_all,
+            "row_start" => &mut self.row_start,
+            "row_end" => &mut self.row_end,
+            "top" => &mut self.top,
+            "bottom" => &mut self.bottom,
+            "new_tab" => &mut self.new_tab,
+            "close_tab" => &mut self.close_tab,
+            "next_tab" => &mut self.next_tab,
+            "prev_tab" => &mut self.prev_tab,
+            "toggle_notes" => &mut self.toggle_notes,
+            "flag_or_reveal" => &mut self.flag_or_reveal,
+            "peek" => &mut self.peek,
+            "toggle_neighbors" => &mut self.toggle_neighbors,
+            _ => return false,
+        };
+        *binding = codes;
+        true
+    }
+
+    fn parse_key(key: &str) -> Option<KeyCode> {
+        let mut chars = key.chars();
+        match (chars.next(), chars.next()) {
+            (Some(c), None) => return Some(KeyCode::Char(c)),
+            (None, _) => return None,
+            _ => (),
+        }
+        match key.to_lowercase().as_str() {
+            "space" => Some(KeyCode::Char(' ')),
+            "esc" => Some(KeyCode::Esc),
+            "enter" => Some(KeyCode::Enter),
+            "tab" => Some(KeyCode::Tab),
+            "backtab" => Some(KeyCode::BackTab),
+            "backspace" => Some(KeyCode::Backspace),
+            "left" => Some(KeyCode::Left),
+            "right" => Some(KeyCode::Right),
+            "up" => Some(KeyCode::Up),
+            "down" => Some(KeyCode::Down),
+            "home" => Some(KeyCode::Home),
+            "end" => Some(KeyCode::End),
+            "pageup" => Some(KeyCode::PageUp),
+            "pagedown" => Some(KeyCode::PageDown),
+            _ => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn hints_follow_rebound_keys() {
+        let mut keymap = Keymap::default();
+        let hints = [(Action::Restart, "New board"), (Action::FlagOrReveal, "Flag"), (Action::Quit, "Quit")];
+        assert_eq!(keymap.hints(&hints), "[r] New board  [Esc] Quit");
+
+        assert!(keymap.bind("restart", &["space".to_string()]));
+        assert!(keymap.bind("flag_or_reveal", &["pagedown".to_string(), "x".to_string()]));
+        assert!(keymap.bind("quit", &[]));
+        assert_eq!(keymap.hints(&hints), "[Space] New board  [PageDown] Flag");
+    }
+}
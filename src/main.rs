@@ -1,32 +1,273 @@
-use std::io::Result;
+use std::{io::Result, path::PathBuf, time::Instant};
 
+mod args;
+mod board;
+mod config;
+mod fuzz;
 mod game;
+mod keymap;
+mod menu;
 mod render;
+mod replay;
+mod score;
+mod scores;
+mod stats;
+mod theme;
+mod timer;
+
+fn save_path() -> PathBuf {
+    dirs::data_dir().unwrap_or_default().join("rust-mines").join("save.json")
+}
+
+// Plays back the saved game's moves, only quitting is accepted meanwhile
+fn replay(args: &args::Args) -> Result<()> {
+    let recorded = match game::Game::load(&save_path()) {
+        Ok(recorded) => recorded,
+        Err(err) => {
+            eprintln!("rust-mines: could not load saved game to replay: {}", err);
+            return Ok(());
+        },
+    };
+    let mut replay = replay::Replay::new(&recorded, args.speed);
+    let glyphs = if args.colorblind { theme::Glyphs::colorblind() } else { theme::Glyphs::default() };
+    let scores = scores::HighScores::load();
+    let mut renderer = render::Screen::new(args.fps, args.keymap.clone())?;
+    renderer.set_cell_width(args.cell_width);
+
+    'replay: loop {
+        replay.advance();
+        if renderer.frame_due() {
+            renderer.render_ui(&replay.game, &scores, &args.theme, &glyphs, &render::Overlays::default())?;
+        }
+        for signal in renderer.handle_events(&replay.game)? {
+            if matches!(signal.signal_type, render::SignalType::Quit) { break 'replay; }
+        }
+    }
+
+    Ok(())
+}
+
+// Lets the player pick the board before the first game, returns false if they quit instead
+fn menu(args: &mut args::Args, renderer: &mut render::Screen) -> Result<bool> {
+    let mut menu = menu::Menu::new(args.width, args.height, args.mines);
+    let mut redraw = true;
+    loop {
+        if redraw || renderer.frame_due() { renderer.render_menu(&menu)?; }
+        let Some((action, code)) = renderer.menu_key()? else {
+            redraw = false;
+            continue;
+        };
+        redraw = true;
+        match menu.key(action, code) {
+            Some(menu::MenuChoice::Start(width, height, mines)) => {
+                (args.width, args.height, args.mines) = (width, height, mines);
+                return Ok(true);
+            },
+            Some(menu::MenuChoice::Quit) => return Ok(false),
+            None => (),
+        }
+    }
+}
+
+// Sizes the board to the terminal, keeping the mine density of the chosen difficulty
+fn fit(args: &mut args::Args, renderer: &render::Screen) -> Result<()> {
+    let (width, height) = renderer.board_fit()?;
+    let (default_width, default_height, default_mines) = args.difficulty.dimensions();
+    let cells = width * height;
+    args.mines = (cells * default_mines / (default_width * default_height)).clamp(1, cells.saturating_sub(1).max(1));
+    (args.width, args.height) = (width, height);
+    Ok(())
+}
+
+// A fresh board set up the way the command line asked for
+fn new_game(args: &args::Args, seed: Option<u64>) -> game::Game {
+    let (width, height, mines) = (args.width, args.height, args.mines);
+    // Layouts were checked while parsing, they stand in for whatever board was asked for
+    let layout = args.layout.as_deref().and_then(|x| game::Game::from_layout(x).ok());
+    let mut game = match (layout, seed) {
+        (Some(layout), _) => layout,
+        (None, seed) if args.solvable => game::Game::new_solvable(width, height, mines, seed.unwrap_or_else(rand::random))
+            .unwrap_or_else(|_| game::Game::from_difficulty(args.difficulty)),
+        (None, Some(seed)) => game::Game::with_mines_seeded(width, height, mines, seed)
+            .or_else(|_| game::Game::new_seeded(width, height, seed))
+            .unwrap_or_else(|_| game::Game::from_difficulty(args.difficulty)),
+        (None, None) => game::Game::with_mines(width, height, mines)
+            .unwrap_or_else(|_| game::Game::from_difficulty(args.difficulty)),
+    };
+    if args.wrap || args.topology != game::Topology::Square { game.reshape(args.wrap, args.topology); }
+    // Crowded boards were turned away while parsing, ones sized later by --fit or the menu
+    // that still don't fit quietly keep mines on the edge, nothing can be printed over the screen
+    if args.safe_border && args.layout.is_none() { let _ = game.set_safe_border(); }
+    game.strict_flags = args.strict_flags;
+    game.auto_open = args.auto_open;
+    game.auto_chord = args.auto_chord;
+    game.safe_reveal = args.safe_reveal;
+    if args.auto_open { game.open(); }
+    game
+}
 
 fn main() -> Result<()> {
-    let (width, height) = (12, 12);
-    let mut renderer = render::Screen::new(60.0)?;
-    let mut game = game::Game::new(width, height);
+    let mut args = args::Args::parse();
+    if args.replay {
+        return replay(&args);
+    }
+    if let Some(steps) = args.fuzz {
+        return fuzz::run(args.seed.unwrap_or_else(rand::random), steps);
+    }
+    let saved = match args.resume {
+        true => game::Game::load(&save_path())
+            .map_err(|err| eprintln!("rust-mines: could not resume saved game: {}", err))
+            .ok(),
+        false => None,
+    };
+    let mut renderer = render::Screen::new(args.fps, args.keymap.clone())?;
+    renderer.set_acceleration(args.accelerate);
+    renderer.set_cell_width(args.cell_width);
+    if args.menu && saved.is_none() && !menu(&mut args, &mut renderer)? {
+        return Ok(());
+    }
+    if args.fit { fit(&mut args, &renderer)?; }
 
-    // TODO: Game start popup with manual size / max window size
-    // TODO: Game won / lost popup with restart / exit game
+    // Only the first board follows --seed, boards opened later are all random
+    let mut games = vec![saved.unwrap_or_else(|| new_game(&args, args.seed))];
+    let mut active = 0;
+    let mut colorblind = args.colorblind;
+    let mut show_probabilities = false;
+    let mut scores = scores::HighScores::load();
+    let mut stats = stats::Stats::load();
+    let mut show_stats = false;
+    let mut animation: Option<render::FloodAnimation> = None;
+    // When the peek key was last seen held down
+    let mut peek: Option<Instant> = None;
 
+    // Frames are only drawn after input, when the clock shows a new second, or while an
+    // animation plays, so an idle board costs next to nothing between polls
+    let mut needs_redraw = true;
+    let mut shown_secs = 0;
     'game: loop {
-        renderer.render_ui(&game).unwrap();
+        let ticked = games[active].elapsed().as_secs() != shown_secs;
+        let animating = animation.is_some() && renderer.frame_due();
+        let peek_ended = peek.is_some_and(|x| x.elapsed() >= render::PEEK_HOLD);
+        if peek_ended { peek = None; }
+        if needs_redraw || ticked || animating || peek_ended {
+            let game = &games[active];
+            shown_secs = game.elapsed().as_secs();
+            let glyphs = if colorblind { theme::Glyphs::colorblind() } else { theme::Glyphs::default() };
+            let probabilities = show_probabilities.then(|| game.mine_probabilities());
+            let concealed = animation.as_ref().map(|x| x.concealed()).unwrap_or_default();
+            if concealed.is_empty() { animation = None; }
+            let overlays = render::Overlays {
+                probabilities: probabilities.as_deref(),
+                stats: show_stats.then_some(&stats),
+                concealed: &concealed,
+                tabs: &games,
+                active_tab: active,
+                peek: peek.map(|_| game.selected),
+            };
+            renderer.render_ui(game, &scores, &args.theme, &glyphs, &overlays)?;
+        }
 
-        for signal in renderer.handle_events(&game)? {
+        let signals = renderer.handle_events(&games[active])?;
+        needs_redraw = !signals.is_empty();
+        for signal in signals {
+            // Switching boards leaves the one switched away from paused
+            let switch_to = match signal.signal_type {
+                render::SignalType::NewTab => {
+                    games.push(new_game(&args, None));
+                    games.len() - 1
+                },
+                render::SignalType::CloseTab if games.len() > 1 => {
+                    games.remove(active);
+                    animation = None;
+                    active = active.min(games.len() - 1);
+                    continue;
+                },
+                render::SignalType::NextTab => (active + 1) % games.len(),
+                render::SignalType::PrevTab => (active + games.len() - 1) % games.len(),
+                _ => active,
+            };
+            if switch_to != active {
+                games[active].suspend();
+                animation = None;
+                active = switch_to;
+            }
+
+            // Quitting asks first while any board has a clock running, there's nothing to lose otherwise
+            let in_progress = games.iter().any(|x| x.timer.running());
+            let game = &mut games[active];
+            let state = game.state;
             match signal.signal_type {
-                render::SignalType::Quit => break 'game,
-                render::SignalType::Move => match signal.pos {
-                    Some(pos) => game.set_selected(pos),
-                    None => (),
+                render::SignalType::Quit if in_progress => renderer.set_confirming_quit(true),
+                render::SignalType::Quit | render::SignalType::ConfirmQuit(true) => break 'game,
+                render::SignalType::ConfirmQuit(false) => renderer.set_confirming_quit(false),
+                render::SignalType::Move => if let Some(pos) = signal.pos {
+                    game.set_selected(pos);
                 },
-                render::SignalType::Click => game.click_tile(),
+                render::SignalType::Click => { game.click_tile(); },
                 render::SignalType::Mark => game.toggle_mark(),
+                render::SignalType::Chord => { game.chord(); },
+                render::SignalType::FlagOrReveal => game.flag_or_reveal(),
+                render::SignalType::Undo => game.undo_mark(),
+                // Fitted boards follow the terminal, so restarts pick up any resize since
+                render::SignalType::Restart if args.fit => {
+                    fit(&mut args, &renderer)?;
+                    *game = new_game(&args, None);
+                },
+                render::SignalType::Restart => game.reset_new(),
+                render::SignalType::RestartSame => game.reset_same(),
+                // Boards nobody has touched yet are refitted right away
+                render::SignalType::Resize => if args.fit && game.moves.is_empty() {
+                    fit(&mut args, &renderer)?;
+                    *game = new_game(&args, None);
+                },
+                render::SignalType::AutoFlag => game.auto_flag(),
+                render::SignalType::Solve => { game.solve(); },
+                render::SignalType::Pause => game.toggle_pause(),
+                render::SignalType::ToggleStats => show_stats = !show_stats,
+                render::SignalType::ToggleRulers => renderer.toggle_rulers(),
+                render::SignalType::ToggleNeighbors => renderer.toggle_neighbors(),
+                render::SignalType::ToggleNotes => renderer.toggle_annotating(),
+                render::SignalType::Annotate(annotation) => game.annotate(annotation),
+                // Giving up is only available in debug runs so it can't be hit by accident
+                render::SignalType::RevealAll => if args.debug { game.reveal_all(); },
+                // Peeking is a teaching and solver debugging aid, it would make the game trivial otherwise
+                render::SignalType::Peek(held) => peek = (held && args.debug).then(Instant::now),
+                render::SignalType::ToggleGlyphs => colorblind = !colorblind,
+                render::SignalType::ToggleProbabilities => show_probabilities = !show_probabilities,
+                render::SignalType::Hint => if let Some((x, y)) = game.hint() {
+                    game.set_selected((x as i32, y as i32));
+                },
+                // Failing to save shouldn't interrupt the game
+                render::SignalType::Save => { let _ = game.save(&save_path()); },
+                render::SignalType::NewTab | render::SignalType::CloseTab
+                | render::SignalType::NextTab | render::SignalType::PrevTab => (),
+            }
+
+            if args.animate && !game.reveal_layers.is_empty() {
+                animation = Some(render::FloodAnimation::new(std::mem::take(&mut game.reveal_layers)));
+            }
+
+            if state != game.state && game.state != game::GameState::Playing {
+                if args.sound {
+                    // One ring for an explosion, two for a win
+                    match game.state {
+                        game::GameState::Won => renderer.bell(2)?,
+                        game::GameState::Lost => renderer.bell(1)?,
+                        _ => (),
+                    }
+                }
+                // Mines of a lost game can go off one wave at a time, starting at the detonation
+                if args.animate_loss && game.state == game::GameState::Lost {
+                    animation = Some(render::FloodAnimation::mines(game.mine_reveal_order()));
+                }
+                scores.record(game);
+                stats.record(game);
+                // Failing to persist scores or stats shouldn't interrupt the game
+                let _ = scores.save();
+                let _ = stats.save();
             }
         }
     }
 
-    renderer.cleanup()?;
     Ok(())
 }
@@ -0,0 +1,78 @@
+use ratatui::crossterm::event::KeyCode;
+
+use crate::{game::Difficulty, keymap::Action};
+
+pub const PRESETS: [Difficulty; 3] = [Difficulty::Beginner, Difficulty::Intermediate, Difficulty::Expert];
+// Width, height and mines of a custom board, typed in as digits
+const FIELD_DIGITS: usize = 4;
+
+pub enum MenuChoice {
+    Start(usize, usize, usize),
+    Quit,
+}
+
+// The start screen: the presets, then a custom board whose fields take typed digits
+pub struct Menu {
+    pub selected: usize,
+    pub fields: [String; 3],
+    pub field: usize,
+    pub error: Option<String>,
+}
+
+impl Menu {
+    pub fn new(width: usize, height: usize, mines: usize) -> Menu {
+        Menu {
+            selected: 0,
+            fields: [width.to_string(), height.to_string(), mines.to_string()],
+            field: 0,
+            error: None,
+        }
+    }
+
+    pub fn custom_selected(&self) -> bool {
+        self.selected == PRESETS.len()
+    }
+
+    pub fn key(&mut self, action: Option<Action>, code: KeyCode) -> Option<MenuChoice> {
+        // Digits and backspace edit the custom fields before any binding gets a look at them
+        if self.custom_selected() {
+            let field = &mut self.fields[self.field];
+            match code {
+                KeyCode::Char(c) if c.is_ascii_digit() => {
+                    if field.len() < FIELD_DIGITS { field.push(c); }
+                    return None;
+                },
+                KeyCode::Backspace => {
+                    field.pop();
+                    return None;
+                },
+                _ => (),
+            }
+        }
+
+        match (action, code) {
+            (_, KeyCode::Enter) | (Some(Action::Click), _) => return self.choice().map(|(width, height, mines)| MenuChoice::Start(width, height, mines)),
+            (Some(Action::Quit), _) => return Some(MenuChoice::Quit),
+            (Some(Action::Up), _) => self.selected = self.selected.saturating_sub(1),
+            (Some(Action::Down), _) => self.selected = (self.selected + 1).min(PRESETS.len()),
+            (Some(Action::Left), _) if self.custom_selected() => self.field = self.field.saturating_sub(1),
+            (Some(Action::Right), _) if self.custom_selected() => self.field = (self.field + 1).min(self.fields.len() - 1),
+            _ => (),
+        }
+        None
+    }
+
+    // Custom boards are checked the same way the command line checks them
+    fn choice(&mut self) -> Option<(usize, usize, usize)> {
+        if let Some(&difficulty) = PRESETS.get(self.selected) {
+            return Some(difficulty.dimensions());
+        }
+
+        let [width, height, mines] = [0, 1, 2].map(|i| self.fields[i].parse::<usize>().unwrap_or(0));
+        if width == 0 || height == 0 || mines == 0 || mines >= width.saturating_mul(height) {
+            self.error = Some(format!("No board is {}x{} with {} mines", width, height, mines));
+            return None;
+        }
+        Some((width, height, mines))
+    }
+}
@@ -1,13 +1,103 @@
-use std::io::{stdout, Result, Stdout};
-use ratatui::{crossterm::{event::{self, DisableMouseCapture, EnableMouseCapture, KeyCode, KeyEventKind, MouseButton, MouseEventKind}, execute, terminal::{disable_raw_mode, enable_raw_mode, EnterAlternateScreen, LeaveAlternateScreen}}, layout::Rect, prelude::CrosstermBackend, style::Stylize, widgets::{block::Title, Block, Paragraph}, CompletedFrame, Frame, Terminal};
+use std::{collections::HashSet, io::{stdout, Result, Stdout, Write}, ops::RangeInclusive, time::{Duration, Instant}};
+use ratatui::{crossterm::{event::{self, DisableMouseCapture, EnableMouseCapture, KeyCode, KeyEvent, KeyEventKind, KeyboardEnhancementFlags, MouseButton, MouseEvent, MouseEventKind, PopKeyboardEnhancementFlags, PushKeyboardEnhancementFlags}, execute, terminal::{disable_raw_mode, enable_raw_mode, supports_keyboard_enhancement, EnterAlternateScreen, LeaveAlternateScreen}}, layout::{Alignment, Rect}, prelude::CrosstermBackend, style::{Color, Style, Stylize}, text::Line, widgets::{block::Title, Block, Borders, Clear, Paragraph}, CompletedFrame, Frame, Terminal};
 
-use crate::game::Game;
+use crate::{game::{Difficulty, Game, GameState, TileType, TileVisibility, Topology}, keymap::{Action, Keymap}, menu::{Menu, PRESETS}, score, scores::HighScores, stats::Stats, theme::{Glyphs, Theme}};
+
+// Tiles are drawn this many columns wide, the widest pads the glyph and leaves room for a note
+pub const CELL_WIDTHS: RangeInclusive<u16> = 1..=3;
+// Input is polled at this rate no matter how rarely frames are drawn
+const POLL_TIMEOUT: Duration = Duration::from_millis(10);
+const STATUS_HEIGHT: u16 = 1;
+// Outside this range frames are either a slideshow or faster than any terminal redraws
+pub const FPS_RANGE: RangeInclusive<f32> = 1.0..=240.0;
+// Narrow boards still get bars wide enough for the title and status text
+const MIN_BAR_WIDTH: u16 = 54;
+// Row numbers on the left, followed by a space
+const RULER_WIDTH: u16 = 4;
+// Presses of the same direction closer together than this count as a held key,
+// which moves one tile further every few repeats up to a limit
+const REPEAT_WINDOW: Duration = Duration::from_millis(80);
+const REPEATS_PER_STEP: u32 = 8;
+const MAX_STEP: u32 = 4;
+// Terminals without keyboard enhancement never report a key coming up, only the presses it
+// repeats while held, so a peek also ends once these go quiet for longer than a typical repeat delay
+pub const PEEK_HOLD: Duration = Duration::from_millis(600);
+// A second left click on the same revealed number within this long of the first chords it
+const DOUBLE_CLICK: Duration = Duration::from_millis(400);
 
 pub enum SignalType {
     Quit,
     Click,
     Mark,
+    // Both mouse buttons pressed together, or a double click, over a number
+    Chord,
     Move,
+    Restart,
+    RestartSame,
+    Save,
+    AutoFlag,
+    Hint,
+    ToggleGlyphs,
+    Undo,
+    ToggleProbabilities,
+    Solve,
+    Pause,
+    ToggleStats,
+    ToggleRulers,
+    ToggleNeighbors,
+    RevealAll,
+    NewTab,
+    CloseTab,
+    NextTab,
+    PrevTab,
+    ToggleNotes,
+    Annotate(Option<char>),
+    FlagOrReveal,
+    // Whether the peek key went down or came back up
+    Peek(bool),
+    // The answer to the quit prompt
+    ConfirmQuit(bool),
+    Resize,
+}
+
+// Time between the waves of an animated flood, mines going off after a loss are slower
+const LAYER_DELAY: Duration = Duration::from_millis(40);
+const MINE_DELAY: Duration = Duration::from_millis(120);
+
+pub struct FloodAnimation {
+    layers: Vec<Vec<(usize, usize)>>,
+    started: Instant,
+    delay: Duration,
+}
+
+impl FloodAnimation {
+    pub fn new(layers: Vec<Vec<(usize, usize)>>) -> FloodAnimation {
+        FloodAnimation { layers, started: Instant::now(), delay: LAYER_DELAY }
+    }
+
+    pub fn mines(layers: Vec<Vec<(usize, usize)>>) -> FloodAnimation {
+        FloodAnimation { delay: MINE_DELAY, ..FloodAnimation::new(layers) }
+    }
+
+    // Layers that aren't due yet stay hidden, one more shows up every delay
+    pub fn concealed(&self) -> Vec<(usize, usize)> {
+        let shown = (self.started.elapsed().as_millis() / self.delay.as_millis()) as usize + 1;
+        self.layers.iter().skip(shown).flatten().copied().collect()
+    }
+}
+
+// Everything drawn over or instead of the plain board
+#[derive(Default)]
+pub struct Overlays<'a> {
+    pub probabilities: Option<&'a [Option<f64>]>,
+    pub stats: Option<&'a Stats>,
+    // Revealed tiles still drawn hidden, for animating floods and the mines of a lost game
+    pub concealed: &'a [(usize, usize)],
+    // Every open board and which one is shown, the strip only appears with more than one
+    pub tabs: &'a [Game],
+    pub active_tab: usize,
+    // A tile drawn as it really is, whatever the player has uncovered
+    pub peek: Option<(usize, usize)>,
 }
 
 pub struct Signal {
@@ -17,157 +107,728 @@ pub struct Signal {
 
 pub struct Screen {
     terminal: Terminal<CrosstermBackend<Stdout>>,
-    interrupt: u64,
+    frame_interval: Duration,
+    last_frame: Option<Instant>,
+    keymap: Keymap,
+    board_origin: (u16, u16),
+    board_size: (usize, usize),
+    hex: bool,
+    cell_width: u16,
+    view_size: (usize, usize),
+    scroll: (usize, usize),
+    // Tile a mouse button went down on, and the tiles a drag has already acted on
+    pressed: Option<(usize, usize)>,
+    dragged: Vec<(usize, usize)>,
+    // Mouse buttons currently down, and whether they've been used for a chord since all were up
+    buttons: Vec<MouseButton>,
+    chorded: bool,
+    // Tile and time of the last plain left click, for spotting a double click
+    last_click: Option<((usize, usize), Instant)>,
+    rulers: bool,
+    // Outline the covered tiles around a selected number, the ones it says something about
+    neighbors: bool,
+    // Letters and digits are typed onto tiles as notes instead of running commands
+    annotating: bool,
+    // Every key answers the quit prompt while it's up
+    confirming_quit: bool,
+    acceleration: bool,
+    // Last move direction, when it was pressed and how many repeats led up to it
+    held: Option<(Action, Instant, u32)>,
+}
+
+// Dropping the screen hands the terminal back, however the game loop was left
+impl Drop for Screen {
+    fn drop(&mut self) {
+        let _ = Screen::restore(self.terminal.backend_mut());
+    }
 }
 
 impl Screen {
-    pub fn new(fps: f32) -> Result<Screen> {
+    pub fn new(fps: f32, keymap: Keymap) -> Result<Screen> {
+        // A panic would otherwise leave the shell in raw mode on the alternate screen,
+        // so the terminal is put back before the panic message is printed
+        let default_hook = std::panic::take_hook();
+        std::panic::set_hook(Box::new(move |info| {
+            let _ = Screen::restore(&mut stdout());
+            default_hook(info);
+        }));
+
         let mut stdout = stdout();
         execute!(stdout, EnterAlternateScreen, EnableMouseCapture)?;
         enable_raw_mode()?;
+        // Repeats and releases are only reported once asked for, where the terminal can tell them apart
+        if supports_keyboard_enhancement().unwrap_or(false) {
+            execute!(stdout, PushKeyboardEnhancementFlags(KeyboardEnhancementFlags::REPORT_EVENT_TYPES))?;
+        }
         let backend = CrosstermBackend::new(stdout);
         let mut terminal = Terminal::new(backend)?;
         terminal.clear()?;
         
         Ok(Screen {
-            terminal: terminal,
-            interrupt: (1000.0 / fps).round() as u64,
+            terminal,
+            frame_interval: Duration::from_secs_f32(1.0 / fps.clamp(*FPS_RANGE.start(), *FPS_RANGE.end())),
+            last_frame: None,
+            keymap,
+            board_origin: (0, 1),
+            board_size: (0, 0),
+            hex: false,
+            cell_width: *CELL_WIDTHS.end(),
+            view_size: (0, 0),
+            scroll: (0, 0),
+            pressed: None,
+            dragged: Vec::new(),
+            buttons: Vec::new(),
+            chorded: false,
+            last_click: None,
+            rulers: false,
+            neighbors: false,
+            annotating: false,
+            confirming_quit: false,
+            acceleration: false,
+            held: None,
         })
     }
 
-    pub fn cleanup(&mut self) -> Result<()> {
-        execute!(
-            self.terminal.backend_mut(),
-            LeaveAlternateScreen,
-            DisableMouseCapture,
-        )?;
-        disable_raw_mode()?;
-        Ok(())
+    fn restore(out: &mut impl Write) -> Result<()> {
+        // Terminals that never had the flags pushed ignore this, and Windows has none to pop
+        let _ = execute!(out, PopKeyboardEnhancementFlags);
+        execute!(out, LeaveAlternateScreen, DisableMouseCapture)?;
+        disable_raw_mode()
+    }
+
+    // Terminals that beep on BEL may collapse back to back rings, so patterns are best kept short
+    pub fn bell(&mut self, rings: usize) -> Result<()> {
+        let backend = self.terminal.backend_mut();
+        backend.write_all("\x07".repeat(rings).as_bytes())?;
+        backend.flush()
     }
 
-    pub fn draw<F>(&mut self, f: F) -> Result<CompletedFrame>
+    pub fn draw<F>(&mut self, f: F) -> Result<CompletedFrame<'_>>
     where F: FnOnce(&mut Frame) {
         self.terminal.draw(f)
     }
 
+    pub fn toggle_rulers(&mut self) {
+        self.rulers = !self.rulers;
+    }
+
+    pub fn toggle_neighbors(&mut self) {
+        self.neighbors = !self.neighbors;
+    }
+
+    pub fn toggle_annotating(&mut self) {
+        self.annotating = !self.annotating;
+    }
+
+    pub fn set_confirming_quit(&mut self, confirming_quit: bool) {
+        self.confirming_quit = confirming_quit;
+    }
+
+    pub fn set_acceleration(&mut self, acceleration: bool) {
+        self.acceleration = acceleration;
+    }
+
+    pub fn set_cell_width(&mut self, cell_width: u16) {
+        self.cell_width = cell_width.clamp(*CELL_WIDTHS.start(), *CELL_WIDTHS.end());
+    }
+
+    fn move_step(&mut self, action: Action) -> i32 {
+        let now = Instant::now();
+        let repeats = match self.held {
+            Some((held, at, repeats)) if held == action && now - at < REPEAT_WINDOW => repeats + 1,
+            _ => 0,
+        };
+        self.held = Some((action, now, repeats));
+
+        if !self.acceleration { return 1; }
+        (1 + repeats / REPEATS_PER_STEP).min(MAX_STEP) as i32
+    }
+
+    // The biggest board that fits the terminal without scrolling, below the title bar and above the status bar
+    pub fn board_fit(&self) -> Result<(usize, usize)> {
+        let area = self.terminal.size()?;
+        Ok((
+            (area.width / self.cell_width).max(1) as usize,
+            area.height.saturating_sub(1 + STATUS_HEIGHT).max(1) as usize,
+        ))
+    }
+
+    pub fn frame_due(&self) -> bool {
+        self.last_frame.is_none_or(|x| x.elapsed() >= self.frame_interval)
+    }
+
+    // Odd rows of hex boards are pushed right by about half a tile, single column tiles can't be
+    fn hex_shift(&self) -> u16 {
+        if self.hex { self.cell_width / 2 } else { 0 }
+    }
+
+    fn row_shift(&self, y: usize) -> u16 {
+        if y % 2 == 1 { self.hex_shift() } else { 0 }
+    }
+
+    pub fn tile_at(&self, col: u16, row: u16) -> Option<(usize, usize)> {
+        let (origin_x, origin_y) = self.board_origin;
+        if col < origin_x || row < origin_y { return None; }
+
+        let y = (row - origin_y) as usize;
+        let shift = self.row_shift(y + self.scroll.1);
+        if col < origin_x + shift { return None; }
+        let x = ((col - origin_x - shift) / self.cell_width) as usize;
+        if x >= self.view_size.0 || y >= self.view_size.1 { return None; }
+
+        let (x, y) = (x + self.scroll.0, y + self.scroll.1);
+        if x < self.board_size.0 && y < self.board_size.1 { Some((x, y)) } else { None }
+    }
+
+    // Scroll just enough to keep the selection in view, without going past the board
+    fn follow_selection(&mut self, selected: (usize, usize)) {
+        let follow = |scroll: usize, selected: usize, view: usize, size: usize| {
+            let scroll = if selected < scroll { selected }
+                else if selected >= scroll + view { selected + 1 - view }
+                else { scroll };
+            scroll.min(size.saturating_sub(view))
+        };
+        self.scroll = (
+            follow(self.scroll.0, selected.0, self.view_size.0, self.board_size.0),
+            follow(self.scroll.1, selected.1, self.view_size.1, self.board_size.1),
+        );
+    }
+
     pub fn handle_events(&mut self, game: &Game) -> Result<Vec<Signal>> {
+        if !event::poll(POLL_TIMEOUT)? {
+            return Ok(Vec::new());
+        }
+
+        // Exactly one event is read per poll, everything below works off that one event
+        let event = event::read()?;
+        if let event::Event::Resize(_, _) = event {
+            // Layout is recomputed from the new size on the next render
+            self.terminal.autoresize()?;
+        }
+        Ok(self.signals_for(&event, game))
+    }
+
+    // The next key press and what the keymap makes of it, for the start menu
+    pub fn menu_key(&mut self) -> Result<Option<(Option<Action>, KeyCode)>> {
+        if !event::poll(POLL_TIMEOUT)? {
+            return Ok(None);
+        }
+        match event::read()? {
+            event::Event::Key(key) if self.triggers(&key) => Ok(Some((self.keymap.action(key.code), key.code))),
+            event::Event::Resize(_, _) => {
+                self.terminal.autoresize()?;
+                Ok(None)
+            },
+            _ => Ok(None),
+        }
+    }
+
+    // Presses trigger every action. Repeats, from terminals that tell them apart from presses,
+    // only move the cursor and keep a peek going, so toggles don't flicker while a key is held.
+    // Releases only ever end a peek
+    fn triggers(&self, key: &KeyEvent) -> bool {
+        match key.kind {
+            KeyEventKind::Press => true,
+            KeyEventKind::Repeat => matches!(
+                self.keymap.action(key.code),
+                Some(Action::Left | Action::Right | Action::Up | Action::Down | Action::Peek)
+            ),
+            KeyEventKind::Release => false,
+        }
+    }
+
+    fn signals_for(&mut self, event: &event::Event, game: &Game) -> Vec<Signal> {
         let mut signals: Vec<Signal> = Vec::new();
 
-        if event::poll(std::time::Duration::from_millis(self.interrupt))? {
-            if let event::Event::Key(key) = event::read()? {
-                if key.kind == KeyEventKind::Press { match key.code {
-                    KeyCode::Esc | KeyCode::Char('q') => signals.push(Signal {
-                        pos: None,
-                        signal_type: SignalType::Quit,
-                    }),
-                    KeyCode::Left | KeyCode::Char('h') => signals.push(Signal {
-                        pos: Some((game.selected.0 as i32 - 1, game.selected.1 as i32)),
-                        signal_type: SignalType::Move,
-                    }),
-                    KeyCode::Right | KeyCode::Char('l') => signals.push(Signal {
-                        pos: Some((game.selected.0 as i32 + 1, game.selected.1 as i32)),
-                        signal_type: SignalType::Move,
-                    }),
-                    KeyCode::Up | KeyCode::Char('k') => signals.push(Signal {
-                        pos: Some((game.selected.0 as i32, game.selected.1 as i32 - 1)),
-                        signal_type: SignalType::Move,
-                    }),
-                    KeyCode::Down | KeyCode::Char('j') => signals.push(Signal {
-                        pos: Some((game.selected.0 as i32, game.selected.1 as i32 + 1)),
-                        signal_type: SignalType::Move,
-                    }),
-                    KeyCode::Char('f') => signals.push(Signal {
-                        pos: None,
-                        signal_type: SignalType::Mark,
-                    }),
-                    KeyCode::Char(' ') => signals.push(Signal {
-                        pos: None,
-                        signal_type: SignalType::Click,
-                    }),
-                    _ => (),
-                } }
-            } else if let event::Event::Mouse(mouse) = event::read()? {
-                signals.push(Signal {
-                    pos: Some((mouse.column as i32 / 3, mouse.row as i32 - 1)),
-                    signal_type: SignalType::Move,
-                });
-
-                match mouse.kind {
-                    MouseEventKind::Up(MouseButton::Left) => signals.push(Signal {
-                        pos: None,
-                        signal_type: SignalType::Click,
-                    }),
-                    MouseEventKind::Up(MouseButton::Right) => signals.push(Signal {
-                        pos: None,
-                        signal_type: SignalType::Mark,
-                    }),
-                    _ => (),
+        match event {
+            event::Event::Key(key) if self.confirming_quit && key.kind == KeyEventKind::Press => {
+                let confirmed = matches!(key.code, KeyCode::Char('y' | 'Y'));
+                signals.push(Signal { pos: None, signal_type: SignalType::ConfirmQuit(confirmed) });
+            },
+            event::Event::Key(key) if self.triggers(key) => {
+                let (x, y) = (game.selected.0 as i32, game.selected.1 as i32);
+                let action = self.keymap.action(key.code);
+                if self.annotating && !game.paused && action != Some(Action::ToggleNotes) {
+                    let annotation = match key.code {
+                        KeyCode::Char(c) if c.is_alphanumeric() => Some(Some(c)),
+                        KeyCode::Backspace | KeyCode::Delete => Some(None),
+                        _ => None,
+                    };
+                    if let Some(annotation) = annotation {
+                        signals.push(Signal { pos: None, signal_type: SignalType::Annotate(annotation) });
+                        return signals;
+                    }
                 }
-            }
+                let step = match action {
+                    Some(action @ (Action::Left | Action::Right | Action::Up | Action::Down)) => self.move_step(action),
+                    _ => 1,
+                };
+                let (mut pos, signal_type) = match action {
+                    Some(Action::Quit) => (None, SignalType::Quit),
+                    Some(Action::Pause) => (None, SignalType::Pause),
+                    Some(Action::NewTab) => (None, SignalType::NewTab),
+                    Some(Action::CloseTab) => (None, SignalType::CloseTab),
+                    Some(Action::NextTab) => (None, SignalType::NextTab),
+                    Some(Action::PrevTab) => (None, SignalType::PrevTab),
+                    // Nothing but unpausing, quitting and switching boards gets through while paused
+                    _ if game.paused => return signals,
+                    Some(Action::Left) => (Some((x - step, y)), SignalType::Move),
+                    Some(Action::Right) => (Some((x + step, y)), SignalType::Move),
+                    Some(Action::Up) => (Some((x, y - step)), SignalType::Move),
+                    Some(Action::Down) => (Some((x, y + step)), SignalType::Move),
+                    // Jumps go straight to the edge of the board, in one row or column
+                    Some(Action::RowStart) => (Some((0, y)), SignalType::Move),
+                    Some(Action::RowEnd) => (Some((game.board.width as i32 - 1, y)), SignalType::Move),
+                    Some(Action::Top) => (Some((x, 0)), SignalType::Move),
+                    Some(Action::Bottom) => (Some((x, game.board.height as i32 - 1)), SignalType::Move),
+                    Some(Action::Click) => (None, SignalType::Click),
+                    Some(Action::Mark) => (None, SignalType::Mark),
+                    Some(Action::Restart) => (None, SignalType::Restart),
+                    Some(Action::RestartSame) => (None, SignalType::RestartSame),
+                    Some(Action::Save) => (None, SignalType::Save),
+                    Some(Action::AutoFlag) => (None, SignalType::AutoFlag),
+                    Some(Action::Hint) => (None, SignalType::Hint),
+                    Some(Action::ToggleGlyphs) => (None, SignalType::ToggleGlyphs),
+                    Some(Action::Undo) => (None, SignalType::Undo),
+                    Some(Action::ToggleProbabilities) => (None, SignalType::ToggleProbabilities),
+                    Some(Action::Solve) => (None, SignalType::Solve),
+                    Some(Action::ToggleStats) => (None, SignalType::ToggleStats),
+                    Some(Action::ToggleRulers) => (None, SignalType::ToggleRulers),
+                    Some(Action::ToggleNeighbors) => (None, SignalType::ToggleNeighbors),
+                    Some(Action::RevealAll) => (None, SignalType::RevealAll),
+                    Some(Action::ToggleNotes) => (None, SignalType::ToggleNotes),
+                    Some(Action::FlagOrReveal) => (None, SignalType::FlagOrReveal),
+                    Some(Action::Peek) => (None, SignalType::Peek(true)),
+                    None => return signals,
+                };
+
+                // Moves stop at the edge, so moving off it is a no-op and sends no signal at all,
+                // except on wrapping boards where the cursor comes back on the opposite side
+                if let Some((to_x, to_y)) = pos {
+                    let (width, height) = (game.board.width as i32, game.board.height as i32);
+                    pos = match game.wrap {
+                        true => Some((to_x.rem_euclid(width), to_y.rem_euclid(height))),
+                        false => {
+                            let to = (to_x.clamp(0, width - 1), to_y.clamp(0, height - 1));
+                            if to == (x, y) { return signals; }
+                            Some(to)
+                        },
+                    };
+                }
+                signals.push(Signal { pos, signal_type });
+            },
+            event::Event::Key(key) if key.kind == KeyEventKind::Release && self.keymap.action(key.code) == Some(Action::Peek) => {
+                signals.push(Signal { pos: None, signal_type: SignalType::Peek(false) });
+            },
+            event::Event::Mouse(mouse) if !game.paused && !self.confirming_quit => signals = self.mouse_signals(mouse, game),
+            event::Event::Resize(_, _) => signals.push(Signal { pos: None, signal_type: SignalType::Resize }),
+            _ => (),
         }
 
-        Ok(signals)
+        signals
     }
 
-    pub fn render_ui(&mut self, game: &Game) -> Result<()> {
-        let tiles = &game.tiles;
-        let (mut widest, mut highest) = (0, 0);
-        tiles.iter().for_each(|x| widest = x.x.max(widest));
-        tiles.iter().for_each(|x| highest = x.y.max(highest));
+    fn mouse_signals(&mut self, mouse: &MouseEvent, game: &Game) -> Vec<Signal> {
+        let mut signals: Vec<Signal> = Vec::new();
+        let action_for = |button: MouseButton| match button {
+            MouseButton::Left => Some(SignalType::Click),
+            MouseButton::Right => Some(SignalType::Mark),
+            _ => None,
+        };
 
-        let mut tile_widgets = Vec::new();
-        for tile in tiles {
-            let widget = match tile.tile_visibility {
-                crate::game::TileVisibility::Hidden => Paragraph::new(" □ ")
-                    .black()
-                    .on_dark_gray(),
-                crate::game::TileVisibility::Marked => Paragraph::new(" ■ ")
-                    .black()
-                    .on_dark_gray(),
+        // There is no timing window, left and right count as pressed together whenever they overlap:
+        // the second going down any time before the first comes back up chords. Neither button's
+        // own action runs when they're released afterwards. Buttons are tracked off the board too
+        let chord = match mouse.kind {
+            MouseEventKind::Down(button) => {
+                let chord = action_for(button).is_some() && self.buttons.iter().any(|&x| x != button && action_for(x).is_some());
+                self.buttons.push(button);
+                self.chorded |= chord;
+                chord
+            },
+            MouseEventKind::Up(button) => {
+                self.buttons.retain(|&x| x != button);
+                false
+            },
+            _ => false,
+        };
+        let chording = self.chorded;
+        if self.buttons.is_empty() { self.chorded = false; }
+
+        let Some(tile) = self.tile_at(mouse.column, mouse.row) else { return signals; };
+        match mouse.kind {
+            MouseEventKind::Down(_) if chord => {
+                signals.push(Signal { pos: Some((tile.0 as i32, tile.1 as i32)), signal_type: SignalType::Move });
+                signals.push(Signal { pos: None, signal_type: SignalType::Chord });
+            },
+            MouseEventKind::Up(_) if chording => self.dragged.clear(),
+            MouseEventKind::Drag(_) if chording => (),
+            MouseEventKind::Down(_) => {
+                self.pressed = Some(tile);
+                self.dragged.clear();
+                signals.push(Signal { pos: Some((tile.0 as i32, tile.1 as i32)), signal_type: SignalType::Move });
+            },
+            // Dragging repeats the button's action on every tile passed over, starting with the pressed one
+            MouseEventKind::Drag(button) => if action_for(button).is_some() {
+                for tile in self.pressed.take().into_iter().chain([tile]) {
+                    if self.dragged.contains(&tile) { continue; }
+                    self.dragged.push(tile);
+                    Screen::drag_signals(tile, action_for(button).unwrap(), game, &mut signals);
+                }
+            },
+            // A plain click, the end of a drag already acted on its tiles
+            MouseEventKind::Up(button) if self.dragged.is_empty() => if let Some(signal_type) = action_for(button) {
+                // The first click of a double click has already done its own thing by the time the second comes
+                let now = Instant::now();
+                let double = button == MouseButton::Left && Screen::is_number(tile, game)
+                    && self.last_click.is_some_and(|(last, at)| last == tile && now.duration_since(at) < DOUBLE_CLICK);
+                self.last_click = if button == MouseButton::Left && !double { Some((tile, now)) } else { None };
+                signals.push(Signal { pos: Some((tile.0 as i32, tile.1 as i32)), signal_type: SignalType::Move });
+                signals.push(Signal { pos: None, signal_type: if double { SignalType::Chord } else { signal_type } });
+            },
+            MouseEventKind::Up(_) => self.dragged.clear(),
+            _ => signals.push(Signal { pos: Some((tile.0 as i32, tile.1 as i32)), signal_type: SignalType::Move }),
+        }
+
+        signals
+    }
+
+    fn is_number(tile: (usize, usize), game: &Game) -> bool {
+        let tile = &game.board[tile];
+        tile.tile_visibility == TileVisibility::Visible && matches!(tile.tile_type, TileType::Safe) && tile.mine_count > 0
+    }
+
+    // Dragging only reveals or flags, it never unflags or chords what it passes over
+    fn drag_signals((x, y): (usize, usize), signal_type: SignalType, game: &Game, signals: &mut Vec<Signal>) {
+        let tile = &game.board[(x, y)];
+        let applies = match signal_type {
+            SignalType::Mark => tile.tile_visibility == TileVisibility::Hidden,
+            _ => tile.is_clickable(),
+        };
+        if applies {
+            signals.push(Signal { pos: Some((x as i32, y as i32)), signal_type: SignalType::Move });
+            signals.push(Signal { pos: None, signal_type });
+        }
+    }
+
+    pub fn render_ui(&mut self, game: &Game, scores: &HighScores, theme: &Theme, glyphs: &Glyphs, overlays: &Overlays) -> Result<()> {
+        // Rulers push the board right of the row numbers and below the column numbers,
+        // with a border on every side. Mouse hit-testing goes through the same origin
+        let border = self.rulers as u16;
+        let (origin_x, origin_y) = if self.rulers { (RULER_WIDTH + border, 2 + border) } else { (0, 1) };
+        let area = self.terminal.size()?;
+        self.board_size = (game.board.width, game.board.height);
+        self.hex = game.topology == Topology::Hex;
+        let shift = self.hex_shift();
+        let cell_width = self.cell_width;
+        self.view_size = (
+            (area.width.saturating_sub(origin_x + border + shift) / cell_width).max(1) as usize,
+            area.height.saturating_sub(origin_y + border + STATUS_HEIGHT).max(1) as usize,
+        );
+        self.follow_selection(game.selected);
+        let (scroll_x, scroll_y) = self.scroll;
+        let (view_width, view_height) = self.view_size;
+        let (shown_width, shown_height) = (game.board.width.min(view_width), game.board.height.min(view_height));
+        let bar_width = (origin_x + shown_width as u16 * cell_width + shift + border).max(MIN_BAR_WIDTH).min(area.width);
+
+        // Everything from the title bar to the status bar is centered as one block,
+        // boards that don't fit end up in the corner and scroll instead
+        let (offset_x, offset_y) = (
+            area.width.saturating_sub(bar_width) / 2,
+            area.height.saturating_sub(origin_y + shown_height as u16 + border + STATUS_HEIGHT) / 2,
+        );
+        self.board_origin = (origin_x + offset_x, origin_y + offset_y);
+        let (origin_x, origin_y) = self.board_origin;
+
+        let concealed: HashSet<&(usize, usize)> = overlays.concealed.iter().collect();
+        let selected_tile = &game.board[game.selected];
+        let constrained: Vec<(usize, usize)> = match self.neighbors {
+            true if selected_tile.tile_visibility == TileVisibility::Visible && selected_tile.mine_count > 0 =>
+                game.neighbors(selected_tile).into_iter().filter(|&x| game.board[x].tile_visibility != TileVisibility::Visible).collect(),
+            _ => Vec::new(),
+        };
+        let mut tiles = Vec::new();
+        // The board is blanked while paused so it can't be studied
+        for tile in game.board.tiles.iter().filter(|_| !game.paused).filter(|x|
+            (scroll_x..scroll_x + view_width).contains(&x.x) &&
+            (scroll_y..scroll_y + view_height).contains(&x.y)
+        ) {
+            let visibility = match concealed.contains(&(tile.x, tile.y)) {
+                _ if overlays.peek == Some((tile.x, tile.y)) => crate::game::TileVisibility::Visible,
+                true => crate::game::TileVisibility::Hidden,
+                false => tile.tile_visibility,
+            };
+            let (glyph, (fg, bg)) = match visibility {
+                crate::game::TileVisibility::Hidden => (glyphs.hidden.to_string(), theme.hidden),
+                crate::game::TileVisibility::Questioned => (glyphs.questioned.to_string(), theme.hidden),
+                // Flags on safe tiles are shown as mistakes once the game is lost
+                crate::game::TileVisibility::Marked => match (game.state, &tile.tile_type) {
+                    (GameState::Lost, crate::game::TileType::Safe) => (glyphs.wrong_mark.to_string(), theme.wrong_mark),
+                    _ => (glyphs.marked.to_string(), theme.marked),
+                },
                 crate::game::TileVisibility::Visible => match tile.tile_type {
-                    crate::game::TileType::Mine => Paragraph::new(" ☓ ")
-                        .dark_gray()
-                        .on_black(),
-                    crate::game::TileType::Safe => Paragraph::new(format!(" {} ",
-                        if tile.mine_count == 0 { "· ".to_string() }
-                        else { tile.mine_count.to_string() }))
-                        .dark_gray()
-                        .on_black(),
+                    crate::game::TileType::Mine if game.detonated == Some((tile.x, tile.y)) => (glyphs.mine.to_string(), theme.exploded),
+                    crate::game::TileType::Mine => (glyphs.mine.to_string(), theme.mine),
+                    crate::game::TileType::Safe => (
+                        if tile.mine_count == 0 { glyphs.empty.to_string() }
+                        else { tile.mine_count.to_string() },
+                        (theme.number(tile.mine_count), theme.revealed.1),
+                    ),
                 },
             };
-            tile_widgets.push((
-                (tile.x * 3) as u16,
-                (tile.y) as u16 + 1,
-                if game.selected == (tile.x, tile.y) { widget.on_yellow() } else { widget },
+            // Notes sit in the tile's right corner until it's revealed, single column tiles have no room for them
+            let note = tile.annotation.filter(|_| visibility != TileVisibility::Visible).unwrap_or(' ');
+            let text = match cell_width {
+                1 => glyph,
+                2 => format!("{}{}", glyph, note),
+                _ => format!(" {}{}", glyph, note),
+            };
+            // Shade frontier tiles from green to red by their estimated mine odds
+            let bg = match overlays.probabilities.and_then(|x| x[game.board.idx(tile.x, tile.y)]) {
+                Some(p) => Color::Rgb((255.0 * p) as u8, (255.0 * (1.0 - p)) as u8, 0),
+                None => bg,
+            };
+            // The selection replaces both colors, so numbers and mines can't blend into it.
+            // The losing click is usually still selected, it keeps its own highlight
+            let selected = game.selected == (tile.x, tile.y) && game.detonated != Some((tile.x, tile.y));
+            let style = match selected {
+                true => Style::new().fg(theme.selection.0).bg(theme.selection.1).bold(),
+                false if constrained.contains(&(tile.x, tile.y)) => Style::new().fg(theme.selection.1).bg(bg).underlined(),
+                false => Style::new().fg(fg).bg(bg),
+            };
+            tiles.push((
+                origin_x + (tile.x - scroll_x) as u16 * cell_width + self.row_shift(tile.y),
+                origin_y + (tile.y - scroll_y) as u16,
+                text,
+                // Dim the board behind the end of game popup
+                if game.state == GameState::Playing { style } else { style.dim() },
             ));
         }
         
+        let title = match game.state {
+            GameState::Playing if game.paused => " Paused",
+            GameState::Playing if self.annotating => " Taking notes",
+            GameState::Playing => " rust-mines",
+            GameState::Won => " Game won ^-^",
+            GameState::Lost => " You exploded >_<",
+            GameState::Surrendered => " Board revealed",
+        };
+        let status_y = origin_y + shown_height as u16 + border;
+        let seed = format!(" {:x} ", game.seed);
+        // Finished boards are tinted by how they ended
+        let tabs = (overlays.tabs.len() > 1).then(|| Line::from(overlays.tabs.iter().enumerate().map(|(i, tab)| {
+            let label = format!(" {} ", i + 1);
+            let label = match tab.state {
+                GameState::Won => label.green(),
+                GameState::Lost => label.red(),
+                _ => label.black(),
+            };
+            if i == overlays.active_tab { label.bold().on_gray() } else { label.on_white() }
+        }).collect::<Vec<_>>()));
+        // Narrow tiles only have room for the last digit of each column
+        let column_modulus = if cell_width < 3 { 10 } else { 100 };
+        let confirming_quit = self.confirming_quit;
+        let pause_hints = self.keymap.hints(&[(Action::Pause, "Resume"), (Action::Quit, "Quit")]);
+        let end_hints = self.keymap.hints(&[(Action::Restart, "New board"), (Action::RestartSame, "Same board"), (Action::Quit, "Quit")]);
+        let stats_hints = self.keymap.hints(&[(Action::ToggleStats, "Close")]);
+        let rulers = self.rulers.then(|| (
+            (scroll_x..scroll_x + shown_width).map(|x| format!("{:^1$}", x % column_modulus, cell_width as usize)).collect::<String>(),
+            (scroll_y..scroll_y + shown_height).map(|y| format!("{:>3} ", y)).collect::<Vec<String>>(),
+        ));
+
         self.draw(|frame| {
+            let area = frame.size();
+
             // Title bar
+            let mut title_bar = Block::new().title(Title::from(title.black().on_white()));
+            if let Some(tabs) = tabs {
+                title_bar = title_bar.title(Title::from(tabs).alignment(Alignment::Center));
+            }
             frame.render_widget(
-                Block::new()
-                    .title(Title::from(" rust-mines".black().on_white()))
+                title_bar
+                    .title(Title::from(seed.dark_gray().on_white())
+                        .alignment(Alignment::Right))
                     .title(Title::from(" X ".black().on_red().bold())
-                        .alignment(ratatui::layout::Alignment::Right))
+                        .alignment(Alignment::Right))
                     .black()
                     .on_white(),
                 Rect::new(
-                    0,
-                    0,
-                    widest as u16 * 3 + 3,
+                    offset_x,
+                    offset_y,
+                    bar_width,
                     1,
-                )
+                ).intersection(area)
             );
 
-            // Tiles
-            for (x, y, widget) in tile_widgets {
+            if let Some((columns, rows)) = rulers {
+                frame.render_widget(
+                    Paragraph::new(columns).dark_gray(),
+                    Rect::new(origin_x, origin_y - 2, shown_width as u16 * cell_width, 1).intersection(area)
+                );
+                for (i, row) in rows.into_iter().enumerate() {
+                    frame.render_widget(
+                        Paragraph::new(row).dark_gray(),
+                        Rect::new(offset_x, origin_y + i as u16, RULER_WIDTH, 1).intersection(area)
+                    );
+                }
                 frame.render_widget(
-                    widget,
-                    Rect::new(x, y, 3, 1)
+                    Block::new().borders(Borders::ALL).dark_gray(),
+                    Rect::new(origin_x - 1, origin_y - 1, shown_width as u16 * cell_width + shift + 2, shown_height as u16 + 2).intersection(area)
                 );
             }
+
+            // Tiles go straight into the buffer, a widget apiece cost more than the text itself
+            let buffer = frame.buffer_mut();
+            for (x, y, text, style) in tiles {
+                if x < area.right() && y < area.bottom() {
+                    buffer.set_stringn(x, y, text, cell_width.min(area.right() - x) as usize, style);
+                }
+            }
+
+            Screen::render_status(frame, game, Rect::new(offset_x, status_y, bar_width, STATUS_HEIGHT).intersection(area));
+            match overlays.stats {
+                Some(stats) => Screen::render_stats(frame, stats, stats_hints),
+                None => Screen::render_popup(frame, game, scores, pause_hints, end_hints),
+            }
+            if confirming_quit {
+                Screen::render_box(frame, vec!["Quit? [y/n]".bold().into()]);
+            }
         })?;
+        self.last_frame = Some(Instant::now());
+
+        Ok(())
+    }
+
+    pub fn render_menu(&mut self, menu: &Menu) -> Result<()> {
+        let mut lines: Vec<Line> = vec!["New game".bold().into()];
+        for (i, difficulty) in PRESETS.iter().enumerate() {
+            let (width, height, mines) = difficulty.dimensions();
+            let line = format!("{:<13} {:>5}, {:>2} mines", format!("{:?}", difficulty), format!("{}x{}", width, height), mines);
+            lines.push(if i == menu.selected { line.reversed().into() } else { line.into() });
+        }
+
+        // The field being typed into is underlined
+        let field = |i: usize| {
+            let text = format!("{:>2}", menu.fields[i]);
+            if menu.custom_selected() && menu.field == i { text.underlined() } else { text.into() }
+        };
+        let label = if menu.custom_selected() { "Custom".reversed() } else { "Custom".into() };
+        lines.push(Line::from(vec![label, "        ".into(), field(0), "x".into(), field(1), ", ".into(), field(2), " mines".into()]));
+
+        if let Some(error) = &menu.error { lines.push(error.clone().red().into()); }
+        lines.push(format!("[Enter] Start  {}", self.keymap.hints(&[(Action::Quit, "Quit")])).into());
 
+        self.draw(|frame| Screen::render_box(frame, lines))?;
+        self.last_frame = Some(Instant::now());
         Ok(())
     }
+
+    fn render_status(frame: &mut Frame, game: &Game, area: Rect) {
+        let difficulty = match Difficulty::matching(game.board.width, game.board.height, game.mine_total) {
+            Some(difficulty) => format!("{:?}", difficulty),
+            None => "Custom".to_string(),
+        };
+        // Capped flags show how many are left to place
+        let flags = match game.strict_flags {
+            true => format!("{}/{}", game.flags_placed(), game.mine_total),
+            false => game.flags_placed().to_string(),
+        };
+        let (x, y) = game.selected;
+        let tile = &game.board[(x, y)];
+        let selected = match (tile.tile_visibility, &tile.tile_type) {
+            (TileVisibility::Hidden, _) => "hidden".to_string(),
+            (TileVisibility::Marked, _) => "flag".to_string(),
+            (TileVisibility::Questioned, _) => "?".to_string(),
+            (TileVisibility::Visible, TileType::Mine) => "mine".to_string(),
+            (TileVisibility::Visible, TileType::Safe) => tile.mine_count.to_string(),
+        };
+        let status = format!(
+            " ☓ {}  ■ {}  {}s  {}%  {}  {},{} {}",
+            game.mines_remaining(),
+            flags,
+            game.elapsed().as_secs(),
+            game.percent_cleared(),
+            difficulty,
+            x,
+            y,
+            selected,
+        );
+
+        frame.render_widget(Paragraph::new(status).black().on_white(), area);
+    }
+
+    fn render_popup(frame: &mut Frame, game: &Game, scores: &HighScores, pause_hints: String, end_hints: String) {
+        let message = match game.state {
+            GameState::Playing if game.paused => return Screen::render_box(frame, vec!["Paused".bold().into(), pause_hints.into()]),
+            GameState::Playing => return,
+            GameState::Won => "Game won ^-^",
+            GameState::Lost => "You exploded >_<",
+            GameState::Surrendered => "Board revealed",
+        };
+        let best = match scores.best(game) {
+            _ if scores.is_new_best(game) => "New best!".to_string(),
+            Some(best) => format!("Best: {}s", best.as_secs()),
+            None => String::new(),
+        };
+        let summary = format!("You cleared {} tiles in {}s.", game.tiles_revealed, game.elapsed().as_secs());
+        let flags = format!("Flags placed: {}", game.flags_used);
+
+        let mut lines: Vec<Line> = vec![message.bold().into(), summary.into(), flags.into()];
+        if let Some(score) = score::of(game) { lines.push(format!("Score: {}", score).into()); }
+        if let Some(efficiency) = game.efficiency().filter(|_| game.state == GameState::Won) {
+            lines.push(format!("3BV: {}, efficiency {:.0}%", game.three_bv(), efficiency * 100.0).into());
+        }
+        if game.no_flag_clear() { lines.push("Cleared without flags!".into()); }
+        if !best.is_empty() { lines.push(best.into()); }
+        lines.push(end_hints.into());
+
+        Screen::render_box(frame, lines);
+    }
+
+    fn render_stats(frame: &mut Frame, stats: &Stats, hints: String) {
+        let mut lines: Vec<Line> = vec!["Statistics".bold().into()];
+        let mut keys: Vec<&String> = stats.records.keys().collect();
+        keys.sort();
+        let secs = |x: Option<Duration>| x.map_or("-".to_string(), |x| format!("{}s", x.as_secs()));
+        for key in keys {
+            let record = &stats.records[key];
+            lines.push(format!(
+                "{}: {} played, {} won ({:.0}%), {} flagless, best {}, avg {}, top score {}",
+                key,
+                record.played,
+                record.wins,
+                record.win_rate() * 100.0,
+                record.no_flag_wins,
+                secs(record.best.map(Duration::from_millis)),
+                secs(record.average()),
+                record.best_score.map_or("-".to_string(), |x| x.to_string()),
+            ).into());
+        }
+        if lines.len() == 1 { lines.push("No games finished yet".into()); }
+        lines.push(hints.into());
+
+        Screen::render_box(frame, lines);
+    }
+
+    fn render_box(frame: &mut Frame, lines: Vec<Line>) {
+        let area = frame.size();
+        let width = (lines.iter().map(|x| x.width()).max().unwrap_or(0) as u16 + 4).min(area.width);
+        let height = (lines.len() as u16 + 2).min(area.height);
+        let popup = Rect::new(
+            area.x + (area.width - width) / 2,
+            area.y + (area.height - height) / 2,
+            width,
+            height,
+        );
+
+        frame.render_widget(Clear, popup);
+        frame.render_widget(
+            Paragraph::new(lines)
+                .alignment(Alignment::Center)
+                .block(Block::new().borders(Borders::ALL))
+                .black()
+                .on_white(),
+            popup
+        );
+    }
 }
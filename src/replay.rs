@@ -0,0 +1,33 @@
+use std::time::Instant;
+
+use crate::game::{Game, Move};
+
+// Plays a recorded game's moves back onto a fresh copy of its board
+pub struct Replay {
+    pub game: Game,
+    moves: Vec<Move>,
+    next: usize,
+    speed: f64,
+    started: Instant,
+}
+
+impl Replay {
+    pub fn new(recorded: &Game, speed: f64) -> Replay {
+        Replay {
+            game: recorded.replay_board(),
+            moves: recorded.moves.clone(),
+            next: 0,
+            speed,
+            started: Instant::now(),
+        }
+    }
+
+    // Applies every move that is due by now at the replay speed
+    pub fn advance(&mut self) {
+        let now = self.started.elapsed().mul_f64(self.speed);
+        while let Some(&mv) = self.moves.get(self.next).filter(|x| x.at <= now) {
+            self.game.apply(mv);
+            self.next += 1;
+        }
+    }
+}
@@ -0,0 +1,50 @@
+use std::time::Duration;
+
+use crate::game::{Game, GameState};
+
+// Added to every time so near instant wins on tiny boards don't dwarf everything else
+const TIME_OFFSET: f64 = 10.0;
+// How much denser boards are weighted up, a board of only mines would count eleven times over
+const DENSITY_WEIGHT: f64 = 10.0;
+
+// Mines cleared, weighted by density, per second taken. More mines on the same board
+// or the same mines in fewer cells score higher, and any board scores lower the longer it took
+pub fn score(width: usize, height: usize, mines: usize, elapsed: Duration) -> u64 {
+    let density = mines as f64 / width.saturating_mul(height).max(1) as f64;
+    let difficulty = mines as f64 * (1.0 + DENSITY_WEIGHT * density);
+    (difficulty * 1000.0 / (elapsed.as_secs_f64() + TIME_OFFSET)).round() as u64
+}
+
+// Only wins are scored
+pub fn of(game: &Game) -> Option<u64> {
+    (game.state == GameState::Won)
+        .then(|| score(game.board.width, game.board.height, game.mine_total, game.elapsed()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::game::Difficulty;
+
+    #[test]
+    fn harder_boards_score_higher_at_equal_time() {
+        for secs in [0, 5, 60, 300] {
+            let elapsed = Duration::from_secs(secs);
+            let scores: Vec<u64> = [Difficulty::Beginner, Difficulty::Intermediate, Difficulty::Expert].iter()
+                .map(|x| { let (width, height, mines) = x.dimensions(); score(width, height, mines, elapsed) })
+                .collect();
+            assert!(scores.windows(2).all(|x| x[0] < x[1]), "{:?} at {}s", scores, secs);
+
+            for mines in 1..80 {
+                assert!(score(9, 9, mines, elapsed) < score(9, 9, mines + 1, elapsed));
+                assert!(score(16, 16, mines, elapsed) < score(9, 9, mines, elapsed));
+            }
+        }
+    }
+
+    #[test]
+    fn slower_wins_score_lower() {
+        let scores: Vec<u64> = (0..100).map(|x| score(16, 16, 40, Duration::from_secs(x * 10))).collect();
+        assert!(scores.windows(2).all(|x| x[0] > x[1]));
+    }
+}
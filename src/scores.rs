@@ -0,0 +1,52 @@
+use std::{collections::HashMap, fs, io::Result, path::PathBuf, time::Duration};
+
+use serde::{Deserialize, Serialize};
+
+use crate::game::{Game, GameState};
+
+#[derive(Default, Serialize, Deserialize)]
+pub struct HighScores {
+    best: HashMap<String, u64>,
+}
+
+impl HighScores {
+    fn path() -> Option<PathBuf> {
+        dirs::config_dir().map(|dir| dir.join("rust-mines").join("scores.json"))
+    }
+
+    fn key(game: &Game) -> String {
+        format!("{}x{}/{}", game.board.width, game.board.height, game.mine_total)
+    }
+
+    // A missing or unreadable file just means no scores yet
+    pub fn load() -> HighScores {
+        HighScores::path()
+            .and_then(|path| fs::read_to_string(path).ok())
+            .and_then(|json| serde_json::from_str(&json).ok())
+            .unwrap_or_default()
+    }
+
+    pub fn save(&self) -> Result<()> {
+        let Some(path) = HighScores::path() else { return Ok(()); };
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+        fs::write(path, serde_json::to_string_pretty(self)?)
+    }
+
+    pub fn best(&self, game: &Game) -> Option<Duration> {
+        self.best.get(&HighScores::key(game)).map(|&millis| Duration::from_millis(millis))
+    }
+
+    pub fn is_new_best(&self, game: &Game) -> bool {
+        game.state == GameState::Won && self.best(game) == Some(Duration::from_millis(game.elapsed().as_millis() as u64))
+    }
+
+    pub fn record(&mut self, game: &Game) {
+        if game.state != GameState::Won { return; }
+
+        let millis = game.elapsed().as_millis() as u64;
+        let best = self.best.entry(HighScores::key(game)).or_insert(u64::MAX);
+        *best = millis.min(*best);
+    }
+}
@@ -0,0 +1,81 @@
+use std::{collections::HashMap, fs, io::Result, path::PathBuf, time::Duration};
+
+use serde::{Deserialize, Serialize};
+
+use crate::{game::{Difficulty, Game, GameState}, score};
+
+#[derive(Default, Serialize, Deserialize)]
+pub struct Record {
+    pub played: u32,
+    pub wins: u32,
+    pub losses: u32,
+    pub best: Option<u64>,
+    // Sum of winning times in millis, for the average
+    pub win_time: u64,
+    #[serde(default)]
+    pub no_flag_wins: u32,
+    #[serde(default)]
+    pub best_score: Option<u64>,
+}
+
+impl Record {
+    pub fn win_rate(&self) -> f64 {
+        if self.played == 0 { 0.0 } else { self.wins as f64 / self.played as f64 }
+    }
+
+    pub fn average(&self) -> Option<Duration> {
+        (self.wins > 0).then(|| Duration::from_millis(self.win_time / self.wins as u64))
+    }
+}
+
+#[derive(Default, Serialize, Deserialize)]
+pub struct Stats {
+    pub records: HashMap<String, Record>,
+}
+
+impl Stats {
+    fn path() -> Option<PathBuf> {
+        dirs::config_dir().map(|dir| dir.join("rust-mines").join("stats.json"))
+    }
+
+    pub fn key(game: &Game) -> String {
+        match Difficulty::matching(game.board.width, game.board.height, game.mine_total) {
+            Some(difficulty) => format!("{:?}", difficulty),
+            None => "Custom".to_string(),
+        }
+    }
+
+    // First runs start from zeros, same as an unreadable file
+    pub fn load() -> Stats {
+        Stats::path()
+            .and_then(|path| fs::read_to_string(path).ok())
+            .and_then(|json| serde_json::from_str(&json).ok())
+            .unwrap_or_default()
+    }
+
+    pub fn save(&self) -> Result<()> {
+        let Some(path) = Stats::path() else { return Ok(()); };
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+        fs::write(path, serde_json::to_string_pretty(self)?)
+    }
+
+    pub fn record(&mut self, game: &Game) {
+        if game.state == GameState::Playing { return; }
+
+        let record = self.records.entry(Stats::key(game)).or_default();
+        record.played += 1;
+        if game.state != GameState::Won {
+            record.losses += 1;
+            return;
+        }
+
+        let millis = game.elapsed().as_millis() as u64;
+        record.wins += 1;
+        if game.no_flag_clear() { record.no_flag_wins += 1; }
+        record.win_time += millis;
+        record.best = Some(record.best.map_or(millis, |best| best.min(millis)));
+        record.best_score = record.best_score.max(score::of(game));
+    }
+}
@@ -0,0 +1,114 @@
+use ratatui::style::Color;
+
+pub struct Theme {
+    pub hidden: (Color, Color),
+    pub marked: (Color, Color),
+    pub wrong_mark: (Color, Color),
+    pub revealed: (Color, Color),
+    pub mine: (Color, Color),
+    // The mine that was clicked, set apart from the ones shown after losing
+    pub exploded: (Color, Color),
+    pub selection: (Color, Color),
+    // Foreground colors for mine counts 1 to 8
+    pub numbers: [Color; 8],
+}
+
+impl Default for Theme {
+    fn default() -> Theme {
+        Theme {
+            hidden: (Color::Black, Color::DarkGray),
+            marked: (Color::Black, Color::DarkGray),
+            wrong_mark: (Color::Red, Color::DarkGray),
+            revealed: (Color::DarkGray, Color::Black),
+            mine: (Color::DarkGray, Color::Black),
+            exploded: (Color::Black, Color::Red),
+            selection: (Color::Black, Color::Yellow),
+            // Classic minesweeper number colors, brightened for a dark background
+            numbers: [
+                Color::LightBlue,
+                Color::Green,
+                Color::LightRed,
+                Color::Blue,
+                Color::Red,
+                Color::Cyan,
+                Color::Magenta,
+                Color::Gray,
+            ],
+        }
+    }
+}
+
+impl Theme {
+    pub fn light() -> Theme {
+        Theme {
+            hidden: (Color::White, Color::Gray),
+            marked: (Color::Red, Color::Gray),
+            wrong_mark: (Color::Black, Color::LightRed),
+            revealed: (Color::Black, Color::White),
+            mine: (Color::Black, Color::LightRed),
+            exploded: (Color::White, Color::Red),
+            selection: (Color::Black, Color::LightBlue),
+            numbers: [
+                Color::Blue,
+                Color::Green,
+                Color::Red,
+                Color::Magenta,
+                Color::LightRed,
+                Color::Cyan,
+                Color::Black,
+                Color::DarkGray,
+            ],
+        }
+    }
+
+    pub fn by_name(name: &str) -> Option<Theme> {
+        match name.to_lowercase().as_str() {
+            "dark" => Some(Theme::default()),
+            "light" => Some(Theme::light()),
+            _ => None,
+        }
+    }
+
+    pub fn number(&self, mine_count: u8) -> Color {
+        match mine_count {
+            1..=8 => self.numbers[mine_count as usize - 1],
+            _ => self.revealed.0,
+        }
+    }
+}
+
+// Colorblind glyphs keep every tile state distinguishable without relying on hue
+pub struct Glyphs {
+    pub hidden: char,
+    pub marked: char,
+    pub questioned: char,
+    pub wrong_mark: char,
+    pub mine: char,
+    pub empty: char,
+}
+
+impl Default for Glyphs {
+    fn default() -> Glyphs {
+        Glyphs {
+            hidden: '□',
+            marked: '■',
+            questioned: '?',
+            wrong_mark: '■',
+            mine: '☓',
+            empty: '·',
+        }
+    }
+}
+
+impl Glyphs {
+    pub fn colorblind() -> Glyphs {
+        Glyphs {
+            hidden: '□',
+            marked: 'F',
+            questioned: '?',
+            wrong_mark: 'X',
+            mine: '*',
+            empty: ' ',
+        }
+    }
+}
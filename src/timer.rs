@@ -0,0 +1,75 @@
+use std::time::{Duration, Instant};
+
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+#[derive(Default)]
+pub struct Timer {
+    start: Option<Instant>,
+    end: Option<Instant>,
+    paused: Option<Instant>,
+}
+
+impl Timer {
+    pub fn start(&mut self) {
+        self.start.get_or_insert_with(Instant::now);
+    }
+
+    pub fn stop(&mut self) {
+        self.end.get_or_insert_with(Instant::now);
+    }
+
+    pub fn running(&self) -> bool {
+        self.start.is_some() && self.end.is_none()
+    }
+
+    pub fn pause(&mut self) {
+        if self.start.is_some() && self.end.is_none() {
+            self.paused.get_or_insert_with(Instant::now);
+        }
+    }
+
+    // Shift the start forward so the paused stretch doesn't count
+    pub fn resume(&mut self) {
+        if let (Some(paused), Some(start)) = (self.paused.take(), self.start) {
+            self.start = Some(start + paused.elapsed());
+        }
+    }
+
+    pub fn elapsed(&self) -> Duration {
+        match (self.start, self.end.or(self.paused)) {
+            (Some(start), Some(end)) => end - start,
+            (Some(start), None) => start.elapsed(),
+            _ => Duration::ZERO,
+        }
+    }
+}
+
+// Instants are meaningless across runs, so only the elapsed time is stored
+#[derive(Serialize, Deserialize)]
+struct SavedTimer {
+    started: bool,
+    stopped: bool,
+    elapsed: Duration,
+}
+
+impl Serialize for Timer {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        SavedTimer {
+            started: self.start.is_some(),
+            stopped: self.end.is_some(),
+            elapsed: self.elapsed(),
+        }.serialize(serializer)
+    }
+}
+
+impl<'de> Deserialize<'de> for Timer {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Timer, D::Error> {
+        let saved = SavedTimer::deserialize(deserializer)?;
+        let now = Instant::now();
+        Ok(Timer {
+            start: saved.started.then(|| now.checked_sub(saved.elapsed).unwrap_or(now)),
+            end: saved.stopped.then_some(now),
+            paused: None,
+        })
+    }
+}